@@ -0,0 +1,76 @@
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use anyhow::{Result, anyhow};
+
+/// Run `process` on every input across `jobs` worker threads, handing each outcome to
+/// `emit` on the calling thread: in input order, or as soon as it is done when
+/// `ordered` is false. An input that fails, even by panicking, only fails its own
+/// outcome; an error from `emit` stops the run.
+pub fn run<I, T>(
+    inputs: &[I],
+    jobs: usize,
+    ordered: bool,
+    process: impl Fn(&I) -> Result<T> + Sync,
+    mut emit: impl FnMut(&I, Result<T>) -> Result<()>,
+) -> Result<()>
+where
+    I: Sync,
+    T: Send,
+{
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, inputs.len().max(1)) {
+            let sender = sender.clone();
+            let (next, process) = (&next, &process);
+            scope.spawn(move || {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(input) = inputs.get(index) else {
+                        break;
+                    };
+                    let outcome = panic::catch_unwind(AssertUnwindSafe(|| process(input)))
+                        .unwrap_or_else(|payload| Err(anyhow!("Extraction panicked: {}", panic_message(&*payload))));
+                    // The receiver is gone when emitting failed; there is nothing left to do
+                    if sender.send((index, outcome)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        // Outcomes that arrived ahead of an earlier input, by index
+        let mut pending = BTreeMap::new();
+        let mut next_to_emit = 0;
+        for (index, outcome) in receiver {
+            if !ordered {
+                emit(&inputs[index], outcome)?;
+                continue;
+            }
+            pending.insert(index, outcome);
+            while let Some(outcome) = pending.remove(&next_to_emit) {
+                emit(&inputs[next_to_emit], outcome)?;
+                next_to_emit += 1;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// The number of workers to use when none is given: one per available CPU
+pub fn default_jobs() -> usize {
+    thread::available_parallelism().map_or(1, |jobs| jobs.get())
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause")
+}
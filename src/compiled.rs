@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+
+use regex::Regex;
+use scraper::Selector;
+use serde_json::{Map, Value};
+use serde_json_path::JsonPath;
+
+use crate::embedded_json;
+use crate::expression::Expression;
+use crate::{XPathRule, xpath_to_css_selector};
+
+/// The selectors, regexes, expressions and JSONPaths of a configuration's rules,
+/// compiled once for every document of a run. Anything that does not compile is left
+/// out, so using it reports the error for each document as it would be without the cache.
+#[derive(Default)]
+pub struct Compiled {
+    selectors: HashMap<String, Selector>,
+    regexes: HashMap<String, Regex>,
+    expressions: HashMap<String, Expression>,
+    jsonpaths: HashMap<String, JsonPath>,
+}
+
+impl Compiled {
+    /// Compile everything `rules` and the rules nested in them use, with the run's
+    /// variables bound in their XPaths
+    pub fn new(rules: &[XPathRule], variables: &Map<String, Value>) -> Self {
+        let mut compiled = Compiled::default();
+        for rule in rules {
+            compiled.add_rule(rule, variables);
+        }
+        compiled
+    }
+
+    /// The compiled selector for an XPath
+    pub fn selector(&self, xpath: &str) -> Option<&Selector> {
+        self.selectors.get(xpath)
+    }
+
+    /// The compiled regex for a pattern
+    pub fn regex(&self, pattern: &str) -> Option<&Regex> {
+        self.regexes.get(pattern)
+    }
+
+    /// The parsed form of a `computed` expression
+    pub fn expression(&self, source: &str) -> Option<&Expression> {
+        self.expressions.get(source)
+    }
+
+    /// The parsed form of a `jsonpath`
+    pub fn jsonpath(&self, path: &str) -> Option<&JsonPath> {
+        self.jsonpaths.get(path)
+    }
+
+    fn add_rule(&mut self, rule: &XPathRule, variables: &Map<String, Value>) {
+        self.add_selector(&rule.xpath, variables);
+        // `regex` and `script_json` rules both read `pattern`
+        self.add_regex(rule.pattern.as_deref());
+        self.add_regex(rule.url_pattern.as_deref());
+        self.add_regex(rule.expect.as_ref().and_then(|expectation| expectation.pattern.as_deref()));
+        if let Some(source) = rule.expression.as_deref()
+            && let Ok(expression) = Expression::parse(source)
+        {
+            self.expressions.insert(source.to_string(), expression);
+        }
+        if let Some(path) = rule.jsonpath.as_deref()
+            && let Ok(parsed) = embedded_json::parse_path(path)
+        {
+            self.jsonpaths.insert(path.to_string(), parsed);
+        }
+        if let Some(condition) = &rule.when {
+            if let Some(xpath) = condition.xpath.as_deref() {
+                self.add_selector(xpath, variables);
+            }
+            self.add_regex(condition.matches.as_deref());
+        }
+        for child in rule.fields.iter().flatten().chain(rule.items.as_deref()) {
+            self.add_rule(child, variables);
+        }
+    }
+
+    fn add_selector(&mut self, xpath: &str, variables: &Map<String, Value>) {
+        if xpath.trim().is_empty() || self.selectors.contains_key(xpath) {
+            return;
+        }
+        if let Ok(css) = xpath_to_css_selector(xpath, variables)
+            && let Ok(selector) = Selector::parse(&css)
+        {
+            self.selectors.insert(xpath.to_string(), selector);
+        }
+    }
+
+    fn add_regex(&mut self, pattern: Option<&str>) {
+        if let Some(pattern) = pattern
+            && let Ok(regex) = Regex::new(pattern)
+        {
+            self.regexes.insert(pattern.to_string(), regex);
+        }
+    }
+}
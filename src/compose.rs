@@ -0,0 +1,275 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde_json::{Map, Value};
+
+use crate::config_format::ConfigFormat;
+use crate::migrate;
+
+/// Keys an included rule file may hold
+const INCLUDABLE: &[&str] = &["rules", "fragments"];
+
+/// Load a configuration with everything it is composed of: the base it `extends`, the
+/// rule files it `include`s and the fragments its rules `use`. Paths are relative to
+/// the file naming them; files whose extension names no format are read as `format`.
+/// Every file is upgraded to the current rule syntax; the deprecation warnings for
+/// files that needed it are returned with the configuration.
+pub fn load(path: &Path, format: Option<ConfigFormat>) -> Result<(Value, Vec<String>)> {
+    let (config, deprecations, _) = load_with_origins(path, format)?;
+    Ok((config, deprecations))
+}
+
+/// [`load`], also returning where each part of the configuration was written
+pub fn load_with_origins(path: &Path, format: Option<ConfigFormat>) -> Result<(Value, Vec<String>, Origins)> {
+    let mut loader = Loader { stack: Vec::new(), deprecations: Vec::new() };
+    let (mut config, mut origins) = loader.file(path, ConfigFormat::resolve(format, path))?;
+    let fragments = match config.shift_remove("fragments") {
+        Some(Value::Object(fragments)) => fragments,
+        Some(_) => bail!("'fragments' must be an object of named rules"),
+        None => Map::new(),
+    };
+    if let Some(Value::Array(rules)) = config.get_mut("rules") {
+        for (index, rule) in rules.iter_mut().enumerate() {
+            expand_rule(rule, &fragments, &mut Vec::new(), &format!("/rules/{}", index), &mut origins)?;
+        }
+    }
+    Ok((Value::Object(config), loader.deprecations, origins))
+}
+
+/// A file and a JSON pointer into it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin {
+    pub file: PathBuf,
+    pub pointer: String,
+}
+
+/// Where each part of a composed configuration was written, by its JSON pointer in the
+/// configuration. A part without an entry was written along with its closest parent
+/// that has one.
+#[derive(Debug, Clone, Default)]
+pub struct Origins(BTreeMap<String, Origin>);
+
+impl Origins {
+    /// Everything in one file; a bare list of rules is read as the file's `rules`
+    fn file(path: &Path, bare_rules: bool) -> Self {
+        let mut origins = Origins::default();
+        origins.0.insert(String::new(), Origin { file: path.to_path_buf(), pointer: String::new() });
+        if bare_rules {
+            origins.0.insert("/rules".to_string(), Origin { file: path.to_path_buf(), pointer: String::new() });
+        }
+        origins
+    }
+
+    /// The file and the pointer in it that `pointer` of the configuration was read from
+    pub fn locate(&self, pointer: &str) -> Origin {
+        let mut prefix = pointer;
+        loop {
+            if let Some(origin) = self.0.get(prefix) {
+                return Origin { file: origin.file.clone(), pointer: format!("{}{}", origin.pointer, &pointer[prefix.len()..]) };
+            }
+            match prefix.rsplit_once('/') {
+                Some((parent, _)) => prefix = parent,
+                None => return Origin { file: PathBuf::new(), pointer: pointer.to_string() },
+            }
+        }
+    }
+
+    /// Record that `to` and everything below it come from `from` in `other`
+    fn graft(&mut self, to: &str, other: &Origins, from: &str) {
+        let below = |pointer: &str, prefix: &str| pointer.strip_prefix(prefix).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'));
+        self.0.retain(|pointer, _| !below(pointer, to));
+        self.0.insert(to.to_string(), other.locate(from));
+        for (pointer, origin) in &other.0 {
+            if let Some(rest) = pointer.strip_prefix(from).filter(|rest| rest.starts_with('/')) {
+                self.0.insert(format!("{}{}", to, rest), origin.clone());
+            }
+        }
+    }
+}
+
+struct Loader {
+    /// Files being loaded, outermost first, to detect cycles
+    stack: Vec<PathBuf>,
+    /// Warnings for files written in legacy rule syntax
+    deprecations: Vec<String>,
+}
+
+impl Loader {
+    fn file(&mut self, path: &Path, format: ConfigFormat) -> Result<(Map<String, Value>, Origins)> {
+        let canonical = path.canonicalize().with_context(|| format!("Cannot find configuration file {:?}", path))?;
+        if let Some(start) = self.stack.iter().position(|loading| *loading == canonical) {
+            let cycle: Vec<String> = self.stack[start..].iter().chain([&canonical]).map(|file| file.display().to_string()).collect();
+            bail!("Configuration files depend on each other in a cycle: {}", cycle.join(" -> "));
+        }
+        self.stack.push(canonical);
+        let composed = self.compose(path, format);
+        self.stack.pop();
+        composed
+    }
+
+    fn compose(&mut self, path: &Path, format: ConfigFormat) -> Result<(Map<String, Value>, Origins)> {
+        let text = fs::read_to_string(path).with_context(|| format!("Failed to read configuration file {:?}", path))?;
+        let mut own = format.parse(&text).with_context(|| format!("Failed to parse configuration file {:?}", path))?;
+        let changes = migrate::upgrade(&mut own).with_context(|| format!("In configuration file {:?}", path))?;
+        if !changes.is_empty() {
+            self.deprecations.push(format!(
+                "{} uses deprecated rule syntax ({}); run `xpath-to-json migrate {}` to update it",
+                path.display(),
+                changes.join("; "),
+                path.display()
+            ));
+        }
+        let own_origins = Origins::file(path, own.is_array());
+        let mut own = match own {
+            Value::Object(own) => own,
+            // A rule file can be a bare list of rules
+            Value::Array(rules) => Map::from_iter([("rules".to_string(), Value::Array(rules))]),
+            _ => bail!("Configuration file {:?} does not hold an object", path),
+        };
+        let directory = path.parent().unwrap_or(Path::new(""));
+        let format_of = |related: &Path| ConfigFormat::from_extension(related).unwrap_or(format);
+
+        let mut composed = Map::new();
+        let mut origins = Origins::file(path, false);
+        if let Some(base) = own.shift_remove("extends") {
+            let base = directory.join(base.as_str().ok_or_else(|| anyhow!("'extends' must be a file path"))?);
+            let (base_config, base_origins) = self.file(&base, format_of(&base)).with_context(|| format!("In the base configuration of {:?}", path))?;
+            for key in base_config.keys() {
+                let pointer = format!("/{}", escape(key));
+                origins.graft(&pointer, &base_origins, &pointer);
+            }
+            composed = base_config;
+        }
+        if let Some(includes) = own.shift_remove("include") {
+            let Value::Array(includes) = includes else {
+                bail!("'include' must be a list of file paths");
+            };
+            for include in includes {
+                let include = directory.join(include.as_str().ok_or_else(|| anyhow!("'include' must be a list of file paths"))?);
+                let (included, included_origins) = self.file(&include, format_of(&include)).with_context(|| format!("In a file included by {:?}", path))?;
+                if let Some(key) = included.keys().find(|key| !INCLUDABLE.contains(&key.as_str())) {
+                    bail!("Included file {:?} can only hold rules and fragments, not '{}'", include, key);
+                }
+                overlay_config(&mut composed, &mut origins, included, &included_origins);
+            }
+        }
+        overlay_config(&mut composed, &mut origins, own, &own_origins);
+        Ok((composed, origins))
+    }
+}
+
+/// Apply `over` on top of `base`: rules are matched by name against those of `base`
+/// and merged field by field, outputs, fragments and variables by key, and anything
+/// else is replaced. A null removes what it overrides. The origins of `base` are
+/// updated with those of `over` for what it sets.
+fn overlay_config(base: &mut Map<String, Value>, base_origins: &mut Origins, over: Map<String, Value>, over_origins: &Origins) {
+    for (key, value) in over {
+        let pointer = format!("/{}", escape(&key));
+        match (base.get_mut(&key), value) {
+            (Some(Value::Array(rules)), Value::Array(overrides)) if key == "rules" => {
+                // Rules of the same file never merge with each other
+                let inherited = rules.len();
+                for (index, rule) in overrides.into_iter().enumerate() {
+                    let from = format!("/rules/{}", index);
+                    match rules[..inherited].iter().position(|existing| rule_name(existing).is_some() && rule_name(existing) == rule_name(&rule)) {
+                        Some(position) if rules[position].is_object() && rule.is_object() => {
+                            for field in rule.as_object().into_iter().flatten().map(|(field, _)| escape(field)) {
+                                base_origins.graft(&format!("/rules/{}/{}", position, field), over_origins, &format!("{}/{}", from, field));
+                            }
+                            if let Value::Object(existing) = &mut rules[position] {
+                                overlay(existing, rule);
+                            }
+                        }
+                        _ => {
+                            base_origins.graft(&format!("/rules/{}", rules.len()), over_origins, &from);
+                            rules.push(rule);
+                        }
+                    }
+                }
+            }
+            (Some(Value::Object(entries)), Value::Object(overrides)) if matches!(key.as_str(), "outputs" | "fragments" | "variables") => {
+                for entry in overrides.keys() {
+                    let entry = format!("{}/{}", pointer, escape(entry));
+                    base_origins.graft(&entry, over_origins, &entry);
+                }
+                overlay(entries, Value::Object(overrides))
+            }
+            (_, Value::Null) => {
+                base.shift_remove(&key);
+            }
+            (_, value) => {
+                base_origins.graft(&pointer, over_origins, &pointer);
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Replace the fields of `base` with those of `over`, removing the ones set to null
+fn overlay(base: &mut Map<String, Value>, over: Value) {
+    let Value::Object(over) = over else {
+        return;
+    };
+    for (key, value) in over {
+        if value.is_null() {
+            base.shift_remove(&key);
+        } else {
+            base.insert(key, value);
+        }
+    }
+}
+
+/// The name a rule is known by: its own, or that of the fragment it uses
+fn rule_name(rule: &Value) -> Option<&str> {
+    rule.get("name").or_else(|| rule.get("use")).and_then(Value::as_str)
+}
+
+/// Replace a rule's `use` with the fragment's fields, overridden by the rule's own, in
+/// the rule and every rule nested in it. `using` holds the fragments being expanded;
+/// `pointer` is where the rule is, for recording where the fields it gets came from.
+fn expand_rule(rule: &mut Value, fragments: &Map<String, Value>, using: &mut Vec<String>, pointer: &str, origins: &mut Origins) -> Result<()> {
+    let Value::Object(fields) = rule else {
+        return Ok(());
+    };
+    if let Some(used) = fields.shift_remove("use") {
+        let name = used.as_str().ok_or_else(|| anyhow!("'use' must name a fragment"))?;
+        if using.iter().any(|fragment| fragment == name) {
+            bail!("Fragments use each other in a cycle: {} -> {}", using.join(" -> "), name);
+        }
+        let Some(fragment) = fragments.get(name).filter(|fragment| fragment.is_object()) else {
+            bail!("Unknown fragment '{}'", name);
+        };
+        let mut fragment = fragment.clone();
+        let fragment_pointer = format!("/fragments/{}", escape(name));
+        using.push(name.to_string());
+        expand_rule(&mut fragment, fragments, using, &fragment_pointer, origins)?;
+        using.pop();
+        let from = origins.clone();
+        for field in fragment.as_object().into_iter().flatten().map(|(field, _)| field).filter(|field| !fields.contains_key(*field)) {
+            let field = escape(field);
+            origins.graft(&format!("{}/{}", pointer, field), &from, &format!("{}/{}", fragment_pointer, field));
+        }
+
+        let mut expanded = Map::from_iter([("name".to_string(), Value::String(name.to_string()))]);
+        overlay(&mut expanded, fragment);
+        overlay(&mut expanded, Value::Object(std::mem::take(fields)));
+        *fields = expanded;
+    }
+
+    if let Some(Value::Array(children)) = fields.get_mut("fields") {
+        for (index, child) in children.iter_mut().enumerate() {
+            expand_rule(child, fragments, using, &format!("{}/fields/{}", pointer, index), origins)?;
+        }
+    }
+    if let Some(items) = fields.get_mut("items") {
+        expand_rule(items, fragments, using, &format!("{}/items", pointer), origins)?;
+    }
+    Ok(())
+}
+
+/// Escape an object key for use in a JSON pointer
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
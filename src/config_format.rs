@@ -0,0 +1,78 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow};
+use clap::ValueEnum;
+use serde_json::Value;
+
+/// Syntax a configuration file is written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ConfigFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl ConfigFormat {
+    /// The format named by a file's extension, if it names one
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "json" => Some(ConfigFormat::Json),
+            "yaml" | "yml" => Some(ConfigFormat::Yaml),
+            "toml" => Some(ConfigFormat::Toml),
+            _ => None,
+        }
+    }
+
+    /// The format to read `path` in: `explicit` when given, otherwise its extension,
+    /// otherwise JSON
+    pub fn resolve(explicit: Option<Self>, path: &Path) -> Self {
+        explicit.or_else(|| Self::from_extension(path)).unwrap_or(ConfigFormat::Json)
+    }
+
+    /// Parse configuration text into its JSON data model
+    pub fn parse(self, text: &str) -> Result<Value> {
+        match self {
+            ConfigFormat::Json => serde_json::from_str(text).map_err(|e| anyhow!("Invalid JSON: {}", e)),
+            ConfigFormat::Yaml => serde_yaml::from_str(text).map_err(|e| anyhow!("Invalid YAML: {}", e)),
+            ConfigFormat::Toml => toml::from_str(text).map_err(|e| anyhow!("Invalid TOML: {}", e.to_string().trim_end())),
+        }
+    }
+
+    /// Write a configuration in this format
+    pub fn write(self, config: &Value) -> Result<String> {
+        match self {
+            ConfigFormat::Json => Ok(serde_json::to_string_pretty(config)? + "\n"),
+            ConfigFormat::Yaml => Ok(serde_yaml::to_string(config)?),
+            ConfigFormat::Toml => {
+                if let Some(pointer) = find_null(config, "") {
+                    return Err(anyhow!("TOML has no null value, found one at '{}'", pointer));
+                }
+                toml::to_string_pretty(config).map_err(|e| anyhow!("Cannot write TOML: {}", e))
+            }
+        }
+    }
+}
+
+/// Read a configuration file in any supported format
+pub fn read(path: &Path, format: Option<ConfigFormat>) -> Result<Value> {
+    let text = fs::read_to_string(path).with_context(|| format!("Failed to read configuration file {:?}", path))?;
+    ConfigFormat::resolve(format, path)
+        .parse(&text)
+        .with_context(|| format!("Failed to parse configuration file {:?}", path))
+}
+
+/// Rewrite a configuration in another format. Comments do not survive the conversion.
+pub fn convert(input: &Path, from: Option<ConfigFormat>, to: ConfigFormat) -> Result<String> {
+    to.write(&read(input, from)?)
+}
+
+/// JSON pointer of the first null in `value`
+fn find_null(value: &Value, pointer: &str) -> Option<String> {
+    match value {
+        Value::Null => Some(pointer.to_string()),
+        Value::Array(items) => items.iter().enumerate().find_map(|(index, item)| find_null(item, &format!("{}/{}", pointer, index))),
+        Value::Object(fields) => fields.iter().find_map(|(key, field)| find_null(field, &format!("{}/{}", pointer, key))),
+        _ => None,
+    }
+}
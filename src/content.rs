@@ -0,0 +1,388 @@
+use scraper::{ElementRef, Node};
+use std::collections::{HashMap, HashSet};
+use url::Url;
+
+/// Tags whose content is always dropped, whatever the allow-list says
+const ALWAYS_STRIPPED_TAGS: &[&str] = &["script", "style"];
+
+/// Clean an HTML fragment down to an allow-list of tags and attributes.
+///
+/// Scripts, styles and `on*` event handler attributes are always removed. With no
+/// explicit lists, ammonia's conservative defaults (formatting, lists, tables, links,
+/// images) are used.
+pub fn sanitize_html(html: &str, allowed_tags: Option<&[String]>, allowed_attributes: Option<&[String]>) -> String {
+    let mut builder = ammonia::Builder::default();
+    builder.link_rel(None);
+
+    if let Some(tags) = allowed_tags {
+        let tags: HashSet<&str> = tags
+            .iter()
+            .map(String::as_str)
+            .filter(|tag| !ALWAYS_STRIPPED_TAGS.contains(tag))
+            .collect();
+        builder.tags(tags);
+    }
+
+    if let Some(attributes) = allowed_attributes {
+        let attributes: HashSet<&str> = attributes
+            .iter()
+            .map(String::as_str)
+            .filter(|attr| !attr.to_ascii_lowercase().starts_with("on"))
+            .collect();
+        builder.generic_attributes(attributes);
+        builder.tag_attributes(HashMap::new());
+    }
+
+    builder.clean(html).to_string().trim().to_string()
+}
+
+/// Elements that never contribute readable content
+const SKIPPED_TAGS: &[&str] = &["script", "style", "noscript", "template", "head", "iframe", "object"];
+
+/// Elements that start a new paragraph-level block
+const BLOCK_TAGS: &[&str] = &[
+    "p", "div", "section", "article", "main", "header", "footer", "aside", "nav", "figure",
+    "figcaption", "address", "details", "summary", "form", "fieldset", "dl", "dt", "dd",
+];
+
+/// Convert an element's subtree to Markdown, keeping headings, paragraphs, lists, links,
+/// emphasis, code and line breaks. Relative links and images are resolved against `base_url`.
+pub fn to_markdown(element: ElementRef, base_url: Option<&Url>) -> String {
+    let mut renderer = Renderer::new(true, base_url);
+    renderer.render_children(element);
+    renderer.finish()
+}
+
+/// Convert an element's subtree to plain text with paragraph, heading, list and line
+/// break structure preserved as blank lines, newlines and bullets
+pub fn to_readable_text(element: ElementRef) -> String {
+    let mut renderer = Renderer::new(false, None);
+    renderer.render_children(element);
+    renderer.finish()
+}
+
+/// An element's text with every run of whitespace collapsed to a single space
+pub fn collapsed_text(element: ElementRef) -> String {
+    element.text().collect::<String>().split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Whether an element is hidden from readers by attribute or inline style
+fn is_hidden(element: &scraper::node::Element) -> bool {
+    if element.attr("hidden").is_some() || element.attr("aria-hidden") == Some("true") {
+        return true;
+    }
+    if element.name() == "input" && element.attr("type") == Some("hidden") {
+        return true;
+    }
+    if let Some(style) = element.attr("style") {
+        let style: String = style.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_ascii_lowercase();
+        return style.contains("display:none") || style.contains("visibility:hidden");
+    }
+    false
+}
+
+struct ListState {
+    ordered: bool,
+    next_number: usize,
+}
+
+/// Streaming text writer that tracks pending line breaks, collapsed whitespace and the
+/// indentation prefix of nested lists and block quotes
+struct Renderer<'a> {
+    markdown: bool,
+    base_url: Option<&'a Url>,
+    out: String,
+    /// Newlines to emit before the next piece of content (1 = line break, 2 = new block)
+    pending_breaks: usize,
+    pending_space: bool,
+    lists: Vec<ListState>,
+    /// Continuation indent for each open list item
+    indents: Vec<String>,
+    quote_depth: usize,
+    /// Shallowest quote depth seen while breaks were pending, used for blank separator lines
+    break_quote_depth: usize,
+    /// Row index within the current table
+    table_row: usize,
+    /// Cell index within the current table row
+    table_cell: usize,
+    /// Output position right after an opening inline marker, where no space may follow
+    marker_end: usize,
+}
+
+impl<'a> Renderer<'a> {
+    fn new(markdown: bool, base_url: Option<&'a Url>) -> Self {
+        Renderer {
+            markdown,
+            base_url,
+            out: String::new(),
+            pending_breaks: 0,
+            pending_space: false,
+            lists: Vec::new(),
+            indents: Vec::new(),
+            quote_depth: 0,
+            break_quote_depth: 0,
+            table_row: 0,
+            table_cell: 0,
+            marker_end: usize::MAX,
+        }
+    }
+
+    fn line_prefix(&self) -> String {
+        let quote = if self.markdown { "> ".repeat(self.quote_depth) } else { String::new() };
+        format!("{}{}", quote, self.indents.concat())
+    }
+
+    fn at_line_start(&self) -> bool {
+        let line_start = format!("\n{}", self.line_prefix());
+        self.out.is_empty() || self.out.ends_with('\n') || self.out.ends_with(&line_start)
+    }
+
+    fn request_break(&mut self, breaks: usize) {
+        // Inside lists a new block is only a new line, otherwise items would become loose
+        let breaks = if breaks > 1 && !self.indents.is_empty() { 1 } else { breaks };
+        // Content directly after a list marker stays on the marker's line
+        if self.out.len() == self.marker_end {
+            return;
+        }
+        if self.pending_breaks == 0 {
+            self.break_quote_depth = self.quote_depth;
+        }
+        self.break_quote_depth = self.break_quote_depth.min(self.quote_depth);
+        self.pending_breaks = self.pending_breaks.max(breaks);
+        self.pending_space = false;
+    }
+
+    /// Emit pending line breaks (with the current prefix) and collapsed whitespace
+    fn flush(&mut self) {
+        if self.pending_breaks > 0 {
+            if !self.out.is_empty() {
+                let prefix = self.line_prefix();
+                let separator_depth = if self.markdown { self.break_quote_depth.min(self.quote_depth) } else { 0 };
+                let separator = "> ".repeat(separator_depth);
+                for i in 0..self.pending_breaks {
+                    self.out.push('\n');
+                    if i + 1 < self.pending_breaks {
+                        self.out.push_str(separator.trim_end());
+                    }
+                }
+                self.out.push_str(&prefix);
+            } else {
+                self.out.push_str(&self.line_prefix());
+            }
+            self.pending_breaks = 0;
+            self.pending_space = false;
+        } else if self.pending_space && !self.out.ends_with(char::is_whitespace) && self.out.len() != self.marker_end && !self.out.is_empty() {
+            self.out.push(' ');
+        }
+        self.pending_space = false;
+    }
+
+    fn write_inline(&mut self, text: &str) {
+        self.flush();
+        self.out.push_str(text);
+    }
+
+    fn write_text(&mut self, text: &str) {
+        if text.starts_with(char::is_whitespace) {
+            self.pending_space = true;
+        }
+        let mut words = text.split_whitespace().peekable();
+        while let Some(word) = words.next() {
+            self.write_inline(word);
+            if words.peek().is_some() {
+                self.pending_space = true;
+            }
+        }
+        if text.ends_with(char::is_whitespace) && !text.trim().is_empty() {
+            self.pending_space = true;
+        }
+    }
+
+    fn render_children(&mut self, element: ElementRef) {
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) => self.write_text(text),
+                Node::Element(_) => {
+                    if let Some(child_element) = ElementRef::wrap(child) {
+                        self.render_element(child_element);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Render children wrapped in inline markers, dropping the markers when nothing was written
+    fn render_wrapped(&mut self, element: ElementRef, open: &str, close: &str) {
+        self.flush();
+        let start = self.out.len();
+        self.out.push_str(open);
+        let content_start = self.out.len();
+        self.marker_end = content_start;
+        self.render_children(element);
+        if self.out[content_start..].trim().is_empty() {
+            self.out.truncate(start);
+        } else {
+            self.out.push_str(close);
+        }
+    }
+
+    fn render_element(&mut self, element: ElementRef) {
+        let value = element.value();
+        let tag = value.name();
+        if SKIPPED_TAGS.contains(&tag) || is_hidden(value) {
+            return;
+        }
+
+        match tag {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.request_break(2);
+                if self.markdown {
+                    let level = tag[1..].parse::<usize>().unwrap_or(1);
+                    self.write_inline(&format!("{} ", "#".repeat(level)));
+                }
+                self.render_children(element);
+                self.request_break(2);
+            }
+            "br" => {
+                if self.markdown && !self.at_line_start() {
+                    self.out.push_str("  ");
+                }
+                self.request_break(1);
+            }
+            "hr" => {
+                self.request_break(2);
+                if self.markdown {
+                    self.write_inline("---");
+                }
+                self.request_break(2);
+            }
+            "ul" | "ol" => {
+                self.request_break(2);
+                self.lists.push(ListState { ordered: tag == "ol", next_number: 1 });
+                self.render_children(element);
+                self.lists.pop();
+                self.request_break(2);
+            }
+            "li" => {
+                let marker = match self.lists.last_mut() {
+                    Some(list) if list.ordered => {
+                        let marker = format!("{}. ", list.next_number);
+                        list.next_number += 1;
+                        marker
+                    }
+                    _ => "- ".to_string(),
+                };
+                self.request_break(1);
+                self.write_inline(&marker);
+                self.marker_end = self.out.len();
+                self.indents.push(" ".repeat(marker.len()));
+                self.render_children(element);
+                self.indents.pop();
+                self.request_break(1);
+            }
+            "blockquote" => {
+                self.request_break(2);
+                self.quote_depth += 1;
+                self.render_children(element);
+                self.quote_depth -= 1;
+                self.request_break(2);
+            }
+            "pre" => {
+                self.request_break(2);
+                let code: String = element.text().collect();
+                let code = code.trim_end_matches('\n');
+                if self.markdown {
+                    self.write_inline("```");
+                    self.request_break(1);
+                }
+                let prefix = self.line_prefix();
+                for (i, line) in code.lines().enumerate() {
+                    if i == 0 {
+                        self.write_inline(line);
+                    } else {
+                        self.out.push('\n');
+                        self.out.push_str(&prefix);
+                        self.out.push_str(line);
+                    }
+                }
+                if self.markdown {
+                    self.request_break(1);
+                    self.write_inline("```");
+                }
+                self.request_break(2);
+            }
+            "table" => {
+                self.request_break(2);
+                let outer_row = self.table_row;
+                self.table_row = 0;
+                self.render_children(element);
+                self.table_row = outer_row;
+                self.request_break(2);
+            }
+            "tr" => {
+                self.request_break(1);
+                self.table_cell = 0;
+                if self.markdown {
+                    self.write_inline("|");
+                }
+                self.render_children(element);
+                if self.markdown {
+                    self.out.push_str(" |");
+                    // Markdown tables need a delimiter row under the first (header) row
+                    if self.table_row == 0 {
+                        let delimiter = format!("\n{}|{}", self.line_prefix(), " --- |".repeat(self.table_cell.max(1)));
+                        self.out.push_str(&delimiter);
+                    }
+                }
+                self.table_row += 1;
+            }
+            "td" | "th" => {
+                let separator = match (self.markdown, self.table_cell) {
+                    (true, 0) => " ",
+                    (false, 0) => "",
+                    _ => " | ",
+                };
+                self.write_inline(separator);
+                self.table_cell += 1;
+                self.render_children(element);
+            }
+            "a" if self.markdown => {
+                let href = value
+                    .attr("href")
+                    .filter(|href| !href.starts_with('#') && !href.starts_with("javascript:"))
+                    .and_then(|href| crate::urls::resolve_url(href, self.base_url, false));
+                match href {
+                    Some(href) => self.render_wrapped(element, "[", &format!("]({})", href)),
+                    None => self.render_children(element),
+                }
+            }
+            "img" if self.markdown => {
+                if let Some(src) = value.attr("src").and_then(|src| crate::urls::resolve_url(src, self.base_url, false)) {
+                    let alt = value.attr("alt").unwrap_or("").trim();
+                    self.write_inline(&format!("![{}]({})", alt, src));
+                }
+            }
+            "strong" | "b" if self.markdown => self.render_wrapped(element, "**", "**"),
+            "em" | "i" if self.markdown => self.render_wrapped(element, "*", "*"),
+            "code" if self.markdown => self.render_wrapped(element, "`", "`"),
+            _ if BLOCK_TAGS.contains(&tag) => {
+                self.request_break(2);
+                self.render_children(element);
+                self.request_break(2);
+            }
+            _ => self.render_children(element),
+        }
+    }
+
+    fn finish(self) -> String {
+        self.out
+            .lines()
+            // Markdown keeps trailing double spaces, they are hard line breaks
+            .map(|line| if line.trim().is_empty() { "" } else if self.markdown { line } else { line.trim_end() })
+            .collect::<Vec<_>>()
+            .join("\n")
+            .trim()
+            .to_string()
+    }
+}
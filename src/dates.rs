@@ -0,0 +1,254 @@
+use anyhow::{Result, anyhow};
+use chrono::{Datelike, NaiveDate, Weekday};
+
+/// Languages whose month and weekday names can be read
+pub const LANGUAGES: &[&str] = &["en", "es", "fr", "de", "pt"];
+
+/// Abbreviations and other spellings of a month or weekday, with its number (1-12 for
+/// months, 0-6 from Monday for weekdays)
+type OtherSpellings = &'static [(&'static str, u32)];
+
+/// Month names, January first, followed by the abbreviations and other spellings in use
+const MONTHS: &[(&str, [&str; 12], OtherSpellings)] = &[
+    (
+        "en",
+        ["january", "february", "march", "april", "may", "june", "july", "august", "september", "october", "november", "december"],
+        &[("jan", 1), ("feb", 2), ("mar", 3), ("apr", 4), ("jun", 6), ("jul", 7), ("aug", 8), ("sep", 9), ("sept", 9), ("oct", 10), ("nov", 11), ("dec", 12)],
+    ),
+    (
+        "es",
+        ["enero", "febrero", "marzo", "abril", "mayo", "junio", "julio", "agosto", "septiembre", "octubre", "noviembre", "diciembre"],
+        &[("ene", 1), ("feb", 2), ("mar", 3), ("abr", 4), ("may", 5), ("jun", 6), ("jul", 7), ("ago", 8), ("sep", 9), ("sept", 9), ("set", 9), ("setiembre", 9), ("oct", 10), ("nov", 11), ("dic", 12)],
+    ),
+    (
+        "fr",
+        ["janvier", "février", "mars", "avril", "mai", "juin", "juillet", "août", "septembre", "octobre", "novembre", "décembre"],
+        &[("janv", 1), ("févr", 2), ("fév", 2), ("avr", 4), ("juil", 7), ("sept", 9), ("oct", 10), ("nov", 11), ("déc", 12)],
+    ),
+    (
+        "de",
+        ["januar", "februar", "märz", "april", "mai", "juni", "juli", "august", "september", "oktober", "november", "dezember"],
+        &[("jan", 1), ("jän", 1), ("jänner", 1), ("feb", 2), ("mär", 3), ("mrz", 3), ("apr", 4), ("jun", 6), ("jul", 7), ("aug", 8), ("sep", 9), ("sept", 9), ("okt", 10), ("nov", 11), ("dez", 12)],
+    ),
+    (
+        "pt",
+        ["janeiro", "fevereiro", "março", "abril", "maio", "junho", "julho", "agosto", "setembro", "outubro", "novembro", "dezembro"],
+        &[("jan", 1), ("fev", 2), ("mar", 3), ("abr", 4), ("mai", 5), ("jun", 6), ("jul", 7), ("ago", 8), ("set", 9), ("out", 10), ("nov", 11), ("dez", 12)],
+    ),
+];
+
+/// Weekday names, Monday first, followed by their abbreviations
+const WEEKDAYS: &[(&str, [&str; 7], OtherSpellings)] = &[
+    (
+        "en",
+        ["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"],
+        &[("mon", 0), ("tue", 1), ("tues", 1), ("wed", 2), ("thu", 3), ("thur", 3), ("thurs", 3), ("fri", 4), ("sat", 5), ("sun", 6)],
+    ),
+    ("es", ["lunes", "martes", "miércoles", "jueves", "viernes", "sábado", "domingo"], &[("lun", 0), ("mar", 1), ("mié", 2), ("jue", 3), ("vie", 4), ("sáb", 5), ("dom", 6)]),
+    ("fr", ["lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche"], &[("lun", 0), ("mar", 1), ("mer", 2), ("jeu", 3), ("ven", 4), ("sam", 5), ("dim", 6)]),
+    (
+        "de",
+        ["montag", "dienstag", "mittwoch", "donnerstag", "freitag", "samstag", "sonntag"],
+        &[("mo", 0), ("di", 1), ("mi", 2), ("do", 3), ("fr", 4), ("sa", 5), ("so", 6), ("mon", 0), ("die", 1), ("mit", 2), ("don", 3), ("fre", 4), ("sam", 5), ("son", 6)],
+    ),
+    ("pt", ["segunda", "terça", "quarta", "quinta", "sexta", "sábado", "domingo"], &[("seg", 0), ("ter", 1), ("qua", 2), ("qui", 3), ("sex", 4), ("sáb", 5), ("dom", 6)]),
+];
+
+/// The language code of a locale name ("pt_BR" is "pt"), if its names can be read
+pub fn language_of(locale: &str) -> Result<String> {
+    let language = locale.trim().split(['_', '-']).next().unwrap_or_default().to_ascii_lowercase();
+    if LANGUAGES.contains(&language.as_str()) {
+        Ok(language)
+    } else {
+        Err(anyhow!("Month and weekday names are not available for '{}' (supported: {})", locale, LANGUAGES.join(", ")))
+    }
+}
+
+/// The month (1-12) a word names in one of `languages`, in full or by one of the
+/// language's abbreviations ("Oct", "sept.", "Dez", "févr")
+pub fn month_from_name(word: &str, languages: &[String]) -> Option<u32> {
+    let spellings = MONTHS.iter().filter(|(language, _, _)| languages.iter().any(|l| l == language)).map(|(_, names, other)| {
+        let names = names.iter().enumerate().map(|(index, name)| (*name, index as u32 + 1));
+        names.chain(other.iter().copied())
+    });
+    name_number(word, spellings.flatten())
+}
+
+/// The weekday a word names in one of `languages`, in full or by one of the language's
+/// abbreviations ("Mon", "mié", "Sa.")
+pub fn weekday_from_name(word: &str, languages: &[String]) -> Option<Weekday> {
+    let spellings = WEEKDAYS.iter().filter(|(language, _, _)| languages.iter().any(|l| l == language)).map(|(_, names, other)| {
+        let names = names.iter().enumerate().map(|(index, name)| (*name, index as u32));
+        names.chain(other.iter().copied())
+    });
+    name_number(word, spellings.flatten()).and_then(|index| Weekday::try_from(index as u8).ok())
+}
+
+/// The number of the spelling `word` is, ignoring case, accents and a trailing dot;
+/// none when spellings with different numbers match
+fn name_number(word: &str, spellings: impl Iterator<Item = (&'static str, u32)>) -> Option<u32> {
+    let word = fold(word.trim().trim_end_matches('.'));
+    let mut found = None;
+    for (spelling, number) in spellings {
+        if fold(spelling) == word {
+            if found.is_some_and(|other| other != number) {
+                return None;
+            }
+            found = Some(number);
+        }
+    }
+    found
+}
+
+/// Read a date written with a month name, in any word order: "October 2025",
+/// "31 de octubre de 2025", "Mi., 3. Dez. 2025", "Oct 31st". A missing day is the
+/// 1st; a missing year is the one that puts the date closest to `today`. A word that
+/// also names a weekday ("mar." is martes as well as marzo) is only read as the month
+/// when no other word is one.
+pub fn parse_textual_date(text: &str, languages: &[String], today: NaiveDate) -> Option<NaiveDate> {
+    let mut month = None;
+    let mut weekday_month = None;
+    let mut day = None;
+    let mut year = None;
+
+    for word in text.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()) {
+        let digits: String = word.chars().take_while(char::is_ascii_digit).collect();
+        if digits.is_empty() {
+            if let Some(found) = month_from_name(word, languages) {
+                let slot = if weekday_from_name(word, languages).is_some() { &mut weekday_month } else { &mut month };
+                slot.get_or_insert(found);
+            }
+            continue;
+        }
+        // Ordinals such as "31st" or "1er" keep their number
+        let Ok(number) = digits.parse::<i32>() else {
+            continue;
+        };
+        if digits.len() == 4 && year.is_none() {
+            year = Some(number);
+        } else if digits.len() <= 2 && (1..=31).contains(&number) && day.is_none() {
+            day = Some(number as u32);
+        }
+    }
+
+    let month = month.or(weekday_month)?;
+    let day = day.unwrap_or(1);
+    match year {
+        Some(year) => NaiveDate::from_ymd_opt(year, month, day),
+        None => [today.year() - 1, today.year(), today.year() + 1]
+            .into_iter()
+            .filter_map(|year| NaiveDate::from_ymd_opt(year, month, day))
+            .min_by_key(|date| (*date - today).num_days().abs()),
+    }
+}
+
+/// Lowercase and drop the accents used in the supported languages, so "Févr", "fevr"
+/// and "FÉVR" compare equal
+fn fold(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .map(|c| match c {
+            'á' | 'à' | 'â' | 'ä' | 'ã' => 'a',
+            'é' | 'è' | 'ê' | 'ë' => 'e',
+            'í' | 'ì' | 'î' | 'ï' => 'i',
+            'ó' | 'ò' | 'ô' | 'ö' | 'õ' => 'o',
+            'ú' | 'ù' | 'û' | 'ü' => 'u',
+            'ç' => 'c',
+            'ñ' => 'n',
+            other => other,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn languages(codes: &[&str]) -> Vec<String> {
+        codes.iter().map(ToString::to_string).collect()
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn month_names_and_abbreviations_are_read_without_case_or_accents() {
+        let all = languages(LANGUAGES);
+        assert_eq!(month_from_name("October", &all), Some(10));
+        assert_eq!(month_from_name("sept.", &all), Some(9));
+        assert_eq!(month_from_name("FÉVR", &all), Some(2));
+        assert_eq!(month_from_name("fevr", &all), Some(2));
+        assert_eq!(month_from_name("Dez", &all), Some(12));
+        assert_eq!(month_from_name("setiembre", &all), Some(9));
+    }
+
+    #[test]
+    fn prefixes_that_are_not_abbreviations_are_not_months() {
+        let all = languages(LANGUAGES);
+        assert_eq!(month_from_name("ago", &languages(&["en"])), None);
+        assert_eq!(month_from_name("junior", &all), None);
+        assert_eq!(month_from_name("octo", &all), None);
+        assert_eq!(month_from_name("marc", &all), None);
+        assert_eq!(weekday_from_name("sunny", &all), None);
+    }
+
+    #[test]
+    fn abbreviations_belong_to_their_language() {
+        assert_eq!(month_from_name("ago", &languages(&["es"])), Some(8));
+        assert_eq!(month_from_name("okt", &languages(&["en"])), None);
+        assert_eq!(month_from_name("okt", &languages(&["de"])), Some(10));
+        assert_eq!(weekday_from_name("Mi.", &languages(&["de"])), Some(Weekday::Wed));
+        assert_eq!(weekday_from_name("mié", &languages(&["es"])), Some(Weekday::Wed));
+        assert_eq!(weekday_from_name("Mon", &languages(&["en"])), Some(Weekday::Mon));
+    }
+
+    #[test]
+    fn abbreviation_shared_by_languages_reads_the_same() {
+        assert_eq!(month_from_name("mai", &languages(&["fr", "de", "pt"])), Some(5));
+        assert_eq!(month_from_name("mar", &languages(&["en", "es", "pt"])), Some(3));
+        assert_eq!(month_from_name("set", &languages(&["es", "pt"])), Some(9));
+    }
+
+    #[test]
+    fn dates_are_read_in_any_word_order() {
+        let today = date(2025, 10, 15);
+        let all = languages(LANGUAGES);
+        assert_eq!(parse_textual_date("October 2025 — Ex-Dividend Calendar", &all, today), Some(date(2025, 10, 1)));
+        assert_eq!(parse_textual_date("31 de octubre de 2025", &all, today), Some(date(2025, 10, 31)));
+        assert_eq!(parse_textual_date("Mi., 3. Dez. 2025", &all, today), Some(date(2025, 12, 3)));
+        assert_eq!(parse_textual_date("Oct 31st", &all, today), Some(date(2025, 10, 31)));
+    }
+
+    #[test]
+    fn weekday_abbreviation_is_not_taken_for_the_month() {
+        let today = date(2025, 10, 15);
+        let spanish = languages(&["es"]);
+        assert_eq!(parse_textual_date("mar., 4 de noviembre de 2025", &spanish, today), Some(date(2025, 11, 4)));
+        assert_eq!(parse_textual_date("4 mar. 2025", &spanish, today), Some(date(2025, 3, 4)));
+    }
+
+    #[test]
+    fn missing_year_is_the_one_closest_to_today() {
+        let english = languages(&["en"]);
+        assert_eq!(parse_textual_date("January 5", &english, date(2025, 12, 20)), Some(date(2026, 1, 5)));
+        assert_eq!(parse_textual_date("December 28", &english, date(2026, 1, 3)), Some(date(2025, 12, 28)));
+        assert_eq!(parse_textual_date("February 29", &english, date(2025, 10, 1)), Some(date(2024, 2, 29)));
+    }
+
+    #[test]
+    fn day_the_month_does_not_have_is_no_date() {
+        let english = languages(&["en"]);
+        let today = date(2025, 10, 15);
+        assert_eq!(parse_textual_date("November 31, 2025", &english, today), None);
+        assert_eq!(parse_textual_date("November 31", &english, today), None);
+        assert_eq!(parse_textual_date("February 29, 2025", &english, today), None);
+    }
+
+    #[test]
+    fn locale_names_map_to_supported_languages() {
+        assert_eq!(language_of("pt_BR").unwrap(), "pt");
+        assert_eq!(language_of("de-AT").unwrap(), "de");
+        assert!(language_of("ja_JP").is_err());
+    }
+}
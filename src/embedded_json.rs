@@ -0,0 +1,99 @@
+use anyhow::{Result, anyhow};
+use regex::Regex;
+use serde_json::Value;
+use serde_json_path::JsonPath;
+
+/// Find and parse the JSON literal in a script's source.
+///
+/// With `pattern`, the regex's first capture group (or whole match) is the literal.
+/// With `assignment` (e.g. `window.__INITIAL_STATE__`), the literal assigned to that
+/// name is used. Otherwise the whole script must be JSON, as in `__NEXT_DATA__`
+/// scripts, or the first object/array literal in it is taken.
+pub fn extract_json(source: &str, pattern: Option<&Regex>, assignment: Option<&str>) -> Result<Value> {
+    let literal = if let Some(regex) = pattern {
+        let captures = regex
+            .captures(source)
+            .ok_or_else(|| anyhow!("Pattern '{}' did not match the script", regex))?;
+        captures.get(1).or_else(|| captures.get(0)).map(|m| m.as_str()).unwrap_or("")
+    } else if let Some(assignment) = assignment {
+        let start = assignment_value_start(source, assignment)
+            .ok_or_else(|| anyhow!("No assignment to '{}' found in the script", assignment))?;
+        balanced_literal(&source[start..])
+            .ok_or_else(|| anyhow!("The value assigned to '{}' is not a JSON object or array", assignment))?
+    } else {
+        let trimmed = source.trim();
+        if let Ok(value) = serde_json::from_str(trimmed) {
+            return Ok(value);
+        }
+        let start = trimmed
+            .find(['{', '['])
+            .ok_or_else(|| anyhow!("No JSON object or array found in the script"))?;
+        balanced_literal(&trimmed[start..]).ok_or_else(|| anyhow!("Unterminated JSON literal in the script"))?
+    };
+
+    serde_json::from_str(literal.trim()).map_err(|e| anyhow!("Embedded JSON is not valid: {}", e))
+}
+
+/// Position of the first `{` or `[` following `name =` (or `name:`) in `source`
+fn assignment_value_start(source: &str, name: &str) -> Option<usize> {
+    let mut search_from = 0;
+    while let Some(found) = source[search_from..].find(name) {
+        let after_name = search_from + found + name.len();
+        let rest = &source[after_name..];
+        let trimmed = rest.trim_start();
+        if let Some(value) = trimmed.strip_prefix('=').or_else(|| trimmed.strip_prefix(':'))
+            && !value.starts_with('=')
+        {
+            let value_offset = after_name + (rest.len() - value.len());
+            let literal_offset = value.len() - value.trim_start().len();
+            if value.trim_start().starts_with(['{', '[']) {
+                return Some(value_offset + literal_offset);
+            }
+        }
+        search_from = after_name;
+    }
+    None
+}
+
+/// The prefix of `source` (which starts with `{` or `[`) up to the matching closing
+/// bracket, skipping brackets inside string literals
+fn balanced_literal(source: &str) -> Option<&str> {
+    let mut depth = 0usize;
+    let mut in_string: Option<char> = None;
+    let mut escaped = false;
+
+    for (index, c) in source.char_indices() {
+        if let Some(quote) = in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == quote {
+                in_string = None;
+            }
+            continue;
+        }
+        match c {
+            '"' | '\'' => in_string = Some(c),
+            '{' | '[' => depth += 1,
+            '}' | ']' => {
+                depth = depth.checked_sub(1)?;
+                if depth == 0 {
+                    return Some(&source[..=index]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parse a JSONPath query
+pub fn parse_path(path: &str) -> Result<JsonPath> {
+    JsonPath::parse(path).map_err(|e| anyhow!("Invalid JSONPath '{}': {}", path, e))
+}
+
+/// Run a JSONPath query and return every node it selects
+pub fn query(value: &Value, path: &JsonPath) -> Vec<Value> {
+    path.query(value).all().into_iter().cloned().collect()
+}
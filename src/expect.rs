@@ -0,0 +1,172 @@
+use std::borrow::Cow;
+
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use url::Url;
+
+use crate::XPathRule;
+use crate::compiled::Compiled;
+use crate::expression::{is_empty_value, to_number, to_text};
+use crate::run_context::RunContext;
+use crate::template::parse_date;
+
+/// What a rule's values should look like; values that do not are reported as warnings,
+/// an early sign that the page layout changed
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct Expectation {
+    /// Regex the text of the value (of each item, for a list) must match
+    pub pattern: Option<String>,
+    /// Kind of value expected (of each item, for a list, unless it is `array`)
+    #[serde(rename = "type")]
+    pub kind: Option<ValueKind>,
+}
+
+/// Kinds of value an [`Expectation`] can ask for
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ValueKind {
+    String,
+    /// A number, or text that reads as one ("$1,299.00", "-0.70%")
+    Number,
+    /// A whole number, or text that reads as one
+    Integer,
+    /// A boolean, or the text "true" or "false"
+    Boolean,
+    Array,
+    Object,
+    /// Text that reads as a date in one of the run's languages
+    Date,
+    /// An absolute URL
+    Url,
+}
+
+/// Problems found checking extracted values against their rules
+#[derive(Debug, Default)]
+pub struct Findings {
+    /// Required values that are missing
+    pub errors: Vec<String>,
+    /// Values that do not look as expected
+    pub warnings: Vec<String>,
+}
+
+/// Check the value a rule extracted (`None` when it produced none) and the values of
+/// its child rules against their `required` and `expect` settings. `label` names the
+/// value in messages.
+pub fn check_rule(rule: &XPathRule, value: Option<&Value>, label: &str, run: &RunContext, compiled: &Compiled, findings: &mut Findings) {
+    if let Some(child) = rule.items.as_deref() {
+        // Grouped values: the rule's own value is each group's key
+        let groups: Vec<&Map<String, Value>> = value.and_then(Value::as_array).map(|groups| groups.iter().filter_map(Value::as_object).collect()).unwrap_or_default();
+        let keys = Value::Array(groups.iter().filter_map(|group| group.get("key").cloned()).collect());
+        check_value(rule, Some(&keys), label, run, compiled, findings);
+        for (index, group) in groups.iter().enumerate() {
+            check_rule(child, group.get(&child.name), &format!("{}[{}].{}", label, index, child.name), run, compiled, findings);
+        }
+        return;
+    }
+    if !check_value(rule, value, label, run, compiled, findings) {
+        return;
+    }
+    let (Some(fields), Some(value)) = (&rule.fields, value) else {
+        return;
+    };
+    let objects: Vec<(String, &Map<String, Value>)> = match value {
+        Value::Object(object) => vec![(label.to_string(), object)],
+        Value::Array(items) => items.iter().enumerate().filter_map(|(index, item)| Some((format!("{}[{}]", label, index), item.as_object()?))).collect(),
+        _ => Vec::new(),
+    };
+    for (object_label, object) in objects {
+        for child in fields {
+            // A field left out by its condition is not missing
+            if object.contains_key(&child.name) || child.when.is_none() {
+                check_rule(child, object.get(&child.name), &format!("{}.{}", object_label, child.name), run, compiled, findings);
+            }
+        }
+    }
+}
+
+/// Check a rule's own value; returns whether there was one
+fn check_value(rule: &XPathRule, value: Option<&Value>, label: &str, run: &RunContext, compiled: &Compiled, findings: &mut Findings) -> bool {
+    let Some(value) = value.filter(|value| !is_empty_value(value)) else {
+        if rule.required.unwrap_or(false) {
+            findings.errors.push(format!("Required field '{}' is missing", label));
+        }
+        return false;
+    };
+    if let Some(expectation) = &rule.expect {
+        match expectation.check(value, run, compiled) {
+            Ok(None) => {}
+            Ok(Some(problem)) => findings.warnings.push(format!("Field '{}' {}", label, problem)),
+            Err(problem) => findings.errors.push(format!("Field '{}': {}", label, problem)),
+        }
+    }
+    true
+}
+
+impl Expectation {
+    /// How `value` falls short of the expectation, if it does, using the pattern as
+    /// compiled for the run. An invalid pattern is an error.
+    pub fn check(&self, value: &Value, run: &RunContext, compiled: &Compiled) -> Result<Option<String>, String> {
+        let pattern = match self.pattern.as_deref() {
+            Some(pattern) => Some(match compiled.regex(pattern) {
+                Some(regex) => Cow::Borrowed(regex),
+                None => Cow::Owned(Regex::new(pattern).map_err(|e| format!("Invalid expect pattern '{}': {}", pattern, e))?),
+            }),
+            None => None,
+        };
+        let items: Vec<&Value> = match value {
+            Value::Array(items) if !matches!(self.kind, Some(ValueKind::Array)) => items.iter().collect(),
+            value => vec![value],
+        };
+
+        let mut failures = Vec::new();
+        for item in &items {
+            if let Some(kind) = self.kind
+                && !kind.matches(item, run)
+            {
+                failures.push((*item, format!("is not {}", kind.described())));
+            } else if let Some(pattern) = &pattern
+                && !pattern.is_match(&to_text(item))
+            {
+                failures.push((*item, format!("does not match '{}'", pattern)));
+            }
+        }
+        Ok(match failures.as_slice() {
+            [] => None,
+            [(item, problem)] if items.len() == 1 => Some(format!("value {} {}", item, problem)),
+            [(item, problem), ..] => Some(format!("has {} of {} values that are not as expected, e.g. {} {}", failures.len(), items.len(), item, problem)),
+        })
+    }
+}
+
+impl ValueKind {
+    fn matches(self, value: &Value, run: &RunContext) -> bool {
+        match (self, value) {
+            (ValueKind::String, value) => value.is_string(),
+            (ValueKind::Number, value) => to_number(value).is_some() && !value.is_boolean(),
+            (ValueKind::Integer, value) => to_number(value).is_some_and(|number| number.fract() == 0.0) && !value.is_boolean(),
+            (ValueKind::Boolean, Value::String(text)) => matches!(text.trim(), "true" | "false"),
+            (ValueKind::Boolean, value) => value.is_boolean(),
+            (ValueKind::Array, value) => value.is_array(),
+            (ValueKind::Object, value) => value.is_object(),
+            (ValueKind::Date, Value::String(text)) => parse_date(text, run).is_some(),
+            (ValueKind::Url, Value::String(text)) => Url::parse(text.trim()).is_ok(),
+            (ValueKind::Date | ValueKind::Url, _) => false,
+        }
+    }
+
+    fn described(self) -> &'static str {
+        match self {
+            ValueKind::String => "text",
+            ValueKind::Number => "a number",
+            ValueKind::Integer => "a whole number",
+            ValueKind::Boolean => "a boolean",
+            ValueKind::Array => "a list",
+            ValueKind::Object => "an object",
+            ValueKind::Date => "a date",
+            ValueKind::Url => "a URL",
+        }
+    }
+}
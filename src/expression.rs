@@ -0,0 +1,550 @@
+//! Expressions for computed fields, e.g. `round(1 - price / original_price, 2)` or
+//! `first + ' ' + last`.
+//!
+//! Field names are bare identifiers (`price`, `item.name` for nested objects) or, for
+//! names that are not identifiers, `field('history-date')`. Extracted text such as
+//! "$1,299.00" is read as a number wherever arithmetic needs one.
+
+use anyhow::{Result, anyhow, bail};
+use serde_json::{Number, Value};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Str(String),
+    Ident(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Comma,
+    Question,
+    Colon,
+}
+
+const OPERATORS: &[&str] = &["==", "!=", "<=", ">=", "&&", "||", "+", "-", "*", "/", "%", "<", ">", "!"];
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || (c == '.' && chars.get(i + 1).is_some_and(char::is_ascii_digit)) {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            tokens.push(Token::Number(text.parse().map_err(|_| anyhow!("Invalid number '{}'", text))?));
+        } else if c == '\'' || c == '"' {
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => bail!("Unterminated string in expression"),
+                    Some('\\') => {
+                        if let Some(next) = chars.get(i + 1) {
+                            text.push(*next);
+                        }
+                        i += 2;
+                    }
+                    Some(q) if *q == c => {
+                        i += 1;
+                        break;
+                    }
+                    Some(other) => {
+                        text.push(*other);
+                        i += 1;
+                    }
+                }
+            }
+            tokens.push(Token::Str(text));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else {
+            match c {
+                '(' => tokens.push(Token::LParen),
+                ')' => tokens.push(Token::RParen),
+                ',' => tokens.push(Token::Comma),
+                '?' => tokens.push(Token::Question),
+                ':' => tokens.push(Token::Colon),
+                _ => {
+                    let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+                    let op = OPERATORS
+                        .iter()
+                        .find(|op| two.starts_with(**op))
+                        .ok_or_else(|| anyhow!("Unexpected character '{}' in expression", c))?;
+                    tokens.push(Token::Op(op));
+                    i += op.len();
+                    continue;
+                }
+            }
+            i += 1;
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Literal(Value),
+    Field(String),
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+/// A parsed expression, ready to be evaluated against extracted fields
+#[derive(Debug, Clone)]
+pub struct Expression {
+    root: Expr,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+/// Binding power of each binary operator; higher binds tighter
+fn precedence(op: &str) -> u8 {
+    match op {
+        "||" => 1,
+        "&&" => 2,
+        "==" | "!=" => 3,
+        "<" | "<=" | ">" | ">=" => 4,
+        "+" | "-" => 5,
+        "*" | "/" | "%" => 6,
+        _ => 0,
+    }
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => bail!("Expected {:?} but found {:?}", expected, token),
+            None => bail!("Expected {:?} but the expression ended", expected),
+        }
+    }
+
+    fn parse_expression(&mut self) -> Result<Expr> {
+        let condition = self.parse_binary(1)?;
+        if self.peek() == Some(&Token::Question) {
+            self.next();
+            let then = self.parse_expression()?;
+            self.expect(Token::Colon)?;
+            let otherwise = self.parse_expression()?;
+            return Ok(Expr::Conditional(Box::new(condition), Box::new(then), Box::new(otherwise)));
+        }
+        Ok(condition)
+    }
+
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expr> {
+        let mut left = self.parse_unary()?;
+        while let Some(Token::Op(op)) = self.peek().cloned() {
+            let op_precedence = precedence(op);
+            if op_precedence == 0 || op_precedence < min_precedence {
+                break;
+            }
+            self.next();
+            let right = self.parse_binary(op_precedence + 1)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr> {
+        match self.peek() {
+            Some(Token::Op("-")) => {
+                self.next();
+                Ok(Expr::Unary("-", Box::new(self.parse_unary()?)))
+            }
+            Some(Token::Op("!")) => {
+                self.next();
+                Ok(Expr::Unary("!", Box::new(self.parse_unary()?)))
+            }
+            _ => self.parse_primary(),
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(Expr::Literal(number_value(n))),
+            Some(Token::Str(s)) => Ok(Expr::Literal(Value::String(s))),
+            Some(Token::LParen) => {
+                let inner = self.parse_expression()?;
+                self.expect(Token::RParen)?;
+                Ok(inner)
+            }
+            Some(Token::Ident(name)) => {
+                if self.peek() == Some(&Token::LParen) {
+                    self.next();
+                    let mut args = Vec::new();
+                    if self.peek() != Some(&Token::RParen) {
+                        loop {
+                            args.push(self.parse_expression()?);
+                            if self.peek() == Some(&Token::Comma) {
+                                self.next();
+                            } else {
+                                break;
+                            }
+                        }
+                    }
+                    self.expect(Token::RParen)?;
+                    if !FUNCTIONS.contains(&name.as_str()) {
+                        bail!("Unknown function '{}'", name);
+                    }
+                    return Ok(Expr::Call(name, args));
+                }
+                Ok(match name.as_str() {
+                    "true" => Expr::Literal(Value::Bool(true)),
+                    "false" => Expr::Literal(Value::Bool(false)),
+                    "null" => Expr::Literal(Value::Null),
+                    _ => Expr::Field(name),
+                })
+            }
+            Some(token) => bail!("Unexpected {:?} in expression", token),
+            None => bail!("Expression ended unexpectedly"),
+        }
+    }
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut parser = Parser { tokens: tokenize(source)?, position: 0 };
+        let root = parser.parse_expression()?;
+        if let Some(token) = parser.peek() {
+            bail!("Unexpected {:?} after the end of the expression", token);
+        }
+        Ok(Expression { root })
+    }
+
+    /// Evaluate against the extracted fields; unknown fields are null
+    pub fn evaluate(&self, fields: &serde_json::Map<String, Value>) -> Result<Value> {
+        evaluate(&self.root, fields)
+    }
+}
+
+fn lookup(fields: &serde_json::Map<String, Value>, path: &str) -> Value {
+    let mut parts = path.split('.');
+    let mut current = parts.next().and_then(|first| fields.get(first));
+    for part in parts {
+        current = current.and_then(|value| match value {
+            Value::Object(object) => object.get(part),
+            Value::Array(items) => part.parse::<usize>().ok().and_then(|index| items.get(index)),
+            _ => None,
+        });
+    }
+    current.cloned().unwrap_or(Value::Null)
+}
+
+pub fn number_value(n: f64) -> Value {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        Value::Number(Number::from(n as i64))
+    } else {
+        Number::from_f64(n).map(Value::Number).unwrap_or(Value::Null)
+    }
+}
+
+/// Read a value as a number, accepting extracted text like "$1,299.00" or "-0.70%"
+pub fn to_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+        Value::String(s) => {
+            let cleaned: String = s
+                .chars()
+                .filter(|c| !c.is_whitespace() && !matches!(c, '$' | '€' | '£' | '¥' | '₹' | ',' | '%'))
+                .collect();
+            cleaned.parse().ok()
+        }
+        _ => None,
+    }
+}
+
+pub fn to_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        Value::Array(items) => items.iter().map(to_text).collect::<Vec<_>>().join(", "),
+        other => other.to_string(),
+    }
+}
+
+pub fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().is_some_and(|n| n != 0.0),
+        other => !is_empty_value(other),
+    }
+}
+
+/// Whether a value holds nothing: null, blank text, or an empty list or object
+pub fn is_empty_value(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::String(text) => text.trim().is_empty(),
+        Value::Array(items) => items.is_empty(),
+        Value::Object(fields) => fields.is_empty(),
+        Value::Bool(_) | Value::Number(_) => false,
+    }
+}
+
+/// Numbers when both sides read as numbers, otherwise text
+fn compare(left: &Value, right: &Value) -> Option<std::cmp::Ordering> {
+    match (to_number(left), to_number(right)) {
+        (Some(a), Some(b)) => a.partial_cmp(&b),
+        _ => Some(to_text(left).cmp(&to_text(right))),
+    }
+}
+
+fn arithmetic(op: &str, left: &Value, right: &Value) -> Value {
+    if left.is_null() || right.is_null() {
+        return Value::Null;
+    }
+    if op == "+" && (to_number(left).is_none() || to_number(right).is_none()) {
+        return Value::String(format!("{}{}", to_text(left), to_text(right)));
+    }
+    let (Some(a), Some(b)) = (to_number(left), to_number(right)) else {
+        return Value::Null;
+    };
+    let result = match op {
+        "+" => a + b,
+        "-" => a - b,
+        "*" => a * b,
+        "/" if b == 0.0 => return Value::Null,
+        "/" => a / b,
+        "%" if b == 0.0 => return Value::Null,
+        _ => a % b,
+    };
+    number_value(result)
+}
+
+fn evaluate(expr: &Expr, fields: &serde_json::Map<String, Value>) -> Result<Value> {
+    Ok(match expr {
+        Expr::Literal(value) => value.clone(),
+        Expr::Field(path) => lookup(fields, path),
+        Expr::Unary(op, inner) => {
+            let value = evaluate(inner, fields)?;
+            match *op {
+                "!" => Value::Bool(!truthy(&value)),
+                _ => to_number(&value).map(|n| number_value(-n)).unwrap_or(Value::Null),
+            }
+        }
+        Expr::Binary(op, left, right) => {
+            // && and || short-circuit and return the deciding operand
+            if *op == "&&" || *op == "||" {
+                let left = evaluate(left, fields)?;
+                let decided = if *op == "&&" { !truthy(&left) } else { truthy(&left) };
+                return if decided { Ok(left) } else { evaluate(right, fields) };
+            }
+            let (left, right) = (evaluate(left, fields)?, evaluate(right, fields)?);
+            match *op {
+                "==" => Value::Bool(compare(&left, &right) == Some(std::cmp::Ordering::Equal)),
+                "!=" => Value::Bool(compare(&left, &right) != Some(std::cmp::Ordering::Equal)),
+                "<" => Value::Bool(compare(&left, &right) == Some(std::cmp::Ordering::Less)),
+                "<=" => Value::Bool(compare(&left, &right).is_some_and(|o| o != std::cmp::Ordering::Greater)),
+                ">" => Value::Bool(compare(&left, &right) == Some(std::cmp::Ordering::Greater)),
+                ">=" => Value::Bool(compare(&left, &right).is_some_and(|o| o != std::cmp::Ordering::Less)),
+                _ => arithmetic(op, &left, &right),
+            }
+        }
+        Expr::Conditional(condition, then, otherwise) => {
+            if truthy(&evaluate(condition, fields)?) {
+                evaluate(then, fields)?
+            } else {
+                evaluate(otherwise, fields)?
+            }
+        }
+        Expr::Call(name, args) => call(name, args, fields)?,
+    })
+}
+
+/// The functions an expression can call, checked when it is parsed
+const FUNCTIONS: &[&str] = &[
+    "if", "field", "number", "string", "text", "round", "floor", "ceil", "abs", "min", "max", "concat", "upper", "lower", "trim",
+    "replace", "len", "coalesce",
+];
+
+fn call(name: &str, args: &[Expr], fields: &serde_json::Map<String, Value>) -> Result<Value> {
+    // if() only evaluates the branch it takes
+    if name == "if" {
+        let [condition, then, otherwise] = args else {
+            bail!("if() takes 3 arguments");
+        };
+        let branch = if truthy(&evaluate(condition, fields)?) { then } else { otherwise };
+        return evaluate(branch, fields);
+    }
+
+    let values: Vec<Value> = args.iter().map(|arg| evaluate(arg, fields)).collect::<Result<_>>()?;
+    let arg = |index: usize| values.get(index).cloned().unwrap_or(Value::Null);
+    let number_arg = |index: usize| to_number(&arg(index));
+
+    Ok(match name {
+        "field" => lookup(fields, &to_text(&arg(0))),
+        "number" => number_arg(0).map(number_value).unwrap_or(Value::Null),
+        "string" | "text" => Value::String(to_text(&arg(0))),
+        "round" => {
+            let digits = number_arg(1).unwrap_or(0.0) as i32;
+            let factor = 10f64.powi(digits);
+            number_arg(0).map(|n| number_value((n * factor).round() / factor)).unwrap_or(Value::Null)
+        }
+        "floor" => number_arg(0).map(|n| number_value(n.floor())).unwrap_or(Value::Null),
+        "ceil" => number_arg(0).map(|n| number_value(n.ceil())).unwrap_or(Value::Null),
+        "abs" => number_arg(0).map(|n| number_value(n.abs())).unwrap_or(Value::Null),
+        "min" | "max" => {
+            // Arrays are spread, so max(prices) works on a multi-valued field
+            let numbers: Vec<f64> = values
+                .iter()
+                .flat_map(|value| match value {
+                    Value::Array(items) => items.clone(),
+                    other => vec![other.clone()],
+                })
+                .filter_map(|value| to_number(&value))
+                .collect();
+            let picked = if name == "min" {
+                numbers.into_iter().reduce(f64::min)
+            } else {
+                numbers.into_iter().reduce(f64::max)
+            };
+            picked.map(number_value).unwrap_or(Value::Null)
+        }
+        "concat" => Value::String(values.iter().map(to_text).collect()),
+        "upper" => Value::String(to_text(&arg(0)).to_uppercase()),
+        "lower" => Value::String(to_text(&arg(0)).to_lowercase()),
+        "trim" => Value::String(to_text(&arg(0)).trim().to_string()),
+        "replace" => Value::String(to_text(&arg(0)).replace(&to_text(&arg(1)), &to_text(&arg(2)))),
+        "len" => match arg(0) {
+            Value::Array(items) => Value::from(items.len()),
+            Value::Null => Value::from(0),
+            other => Value::from(to_text(&other).chars().count()),
+        },
+        "coalesce" => values.into_iter().find(truthy).unwrap_or(Value::Null),
+        _ => bail!("Unknown function '{}'", name),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn eval_with(source: &str, fields: Value) -> Value {
+        let Value::Object(fields) = fields else {
+            panic!("fields must be an object");
+        };
+        Expression::parse(source).unwrap().evaluate(&fields).unwrap()
+    }
+
+    fn eval(source: &str) -> Value {
+        eval_with(source, json!({}))
+    }
+
+    fn parse_error(source: &str) -> String {
+        Expression::parse(source).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn multiplication_binds_tighter_than_addition() {
+        assert_eq!(eval("1 + 2 * 3"), json!(7));
+        assert_eq!(eval("(1 + 2) * 3"), json!(9));
+        assert_eq!(eval("2 * 3 % 4"), json!(2));
+        assert_eq!(eval("-2 * 3"), json!(-6));
+    }
+
+    #[test]
+    fn operators_of_equal_precedence_group_left_to_right() {
+        assert_eq!(eval("10 - 4 - 3"), json!(3));
+        assert_eq!(eval("64 / 4 / 2"), json!(8));
+    }
+
+    #[test]
+    fn comparisons_bind_looser_than_arithmetic_and_tighter_than_logic() {
+        assert_eq!(eval("1 + 1 == 2"), json!(true));
+        assert_eq!(eval("2 < 1 + 2 && 3 >= 3"), json!(true));
+        assert_eq!(eval("1 == 1 != false"), json!(true));
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(eval("true || false && false"), json!(true));
+        assert_eq!(eval("(true || false) && false"), json!(false));
+        assert_eq!(eval("!0 && !''"), json!(true));
+    }
+
+    #[test]
+    fn conditional_has_the_lowest_precedence_and_nests_to_the_right() {
+        assert_eq!(eval_with("price > 100 ? 'high' : 'low'", json!({ "price": "$120" })), json!("high"));
+        assert_eq!(eval_with("a ? 1 : b ? 2 : 3", json!({ "a": false, "b": true })), json!(2));
+        assert_eq!(eval_with("a || b ? 'some' : 'none'", json!({ "a": "", "b": null })), json!("none"));
+    }
+
+    #[test]
+    fn logic_returns_the_deciding_operand() {
+        assert_eq!(eval_with("name || 'unknown'", json!({ "name": "" })), json!("unknown"));
+        assert_eq!(eval_with("name && upper(name)", json!({ "name": "ada" })), json!("ADA"));
+    }
+
+    #[test]
+    fn extracted_text_is_read_as_a_number_for_arithmetic() {
+        let fields = json!({ "price": "$1,299.00", "original_price": "1,500", "first": "Ada", "last": "Lovelace" });
+        assert_eq!(eval_with("price * 2", fields.clone()), json!(2598));
+        assert_eq!(eval_with("round(1 - price / original_price, 2)", fields.clone()), json!(0.13));
+        assert_eq!(eval_with("first + ' ' + last", fields), json!("Ada Lovelace"));
+    }
+
+    #[test]
+    fn missing_values_and_division_by_zero_give_null() {
+        assert_eq!(eval_with("missing * 2", json!({})), Value::Null);
+        assert_eq!(eval("1 / 0"), Value::Null);
+        assert_eq!(eval("1 % 0"), Value::Null);
+    }
+
+    #[test]
+    fn fields_are_found_by_path_or_by_name() {
+        let fields = json!({ "item": { "prices": [3, "7", 5] }, "history-date": "2025-10-31" });
+        assert_eq!(eval_with("item.prices.1", fields.clone()), json!("7"));
+        assert_eq!(eval_with("max(item.prices)", fields.clone()), json!(7));
+        assert_eq!(eval_with("field('history-date')", fields), json!("2025-10-31"));
+    }
+
+    #[test]
+    fn syntax_errors_are_reported_when_parsing() {
+        assert_eq!(parse_error("1 +"), "Expression ended unexpectedly");
+        assert_eq!(parse_error("(1 + 2"), "Expected RParen but the expression ended");
+        assert_eq!(parse_error("1 2"), "Unexpected Number(2.0) after the end of the expression");
+        assert_eq!(parse_error("'open"), "Unterminated string in expression");
+        assert_eq!(parse_error("1 # 2"), "Unexpected character '#' in expression");
+        assert_eq!(parse_error("a ? 1"), "Expected Colon but the expression ended");
+    }
+
+    #[test]
+    fn unknown_functions_are_reported_when_parsing() {
+        assert_eq!(parse_error("rund(price, 2)"), "Unknown function 'rund'");
+        assert!(Expression::parse("round(price, 2)").is_ok());
+    }
+
+    #[test]
+    fn if_needs_three_arguments() {
+        let expression = Expression::parse("if(a, 1)").unwrap();
+        assert_eq!(expression.evaluate(&serde_json::Map::new()).unwrap_err().to_string(), "if() takes 3 arguments");
+    }
+}
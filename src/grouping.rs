@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+
+use anyhow::{Result, bail};
+use ego_tree::NodeId;
+use scraper::ElementRef;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::content::collapsed_text;
+
+/// How the nodes of an `items` rule are attached to the nodes of the rule above it
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Association {
+    /// The one parent inside the closest ancestor that contains any parent, e.g. the
+    /// header of the table or section the node is in
+    #[default]
+    Container,
+    /// The parent in the same table column, in the nearest row above that holds
+    /// parents, e.g. the day number above a calendar cell
+    Column,
+    /// The last parent before the node in document order, e.g. the heading a
+    /// paragraph follows
+    Preceding,
+}
+
+/// For each of `children`, the index of the parent it belongs to. A child whose
+/// parent cannot be determined unambiguously is an error.
+pub fn associate(parents: &[ElementRef], children: &[ElementRef], association: Association) -> Result<Vec<usize>> {
+    match association {
+        Association::Container => by_container(parents, children),
+        Association::Column => by_column(parents, children),
+        Association::Preceding => by_preceding(parents, children),
+    }
+}
+
+fn by_container(parents: &[ElementRef], children: &[ElementRef]) -> Result<Vec<usize>> {
+    // Every element containing a parent, and which parents it contains
+    let mut holders: HashMap<NodeId, Vec<usize>> = HashMap::new();
+    for (index, parent) in parents.iter().enumerate() {
+        for id in self_and_ancestors(parent) {
+            holders.entry(id).or_default().push(index);
+        }
+    }
+
+    children
+        .iter()
+        .map(|child| {
+            let Some(found) = self_and_ancestors(child).find_map(|id| holders.get(&id)) else {
+                bail!("{} is not inside an element containing any candidate", describe(child));
+            };
+            match found.as_slice() {
+                [only] => Ok(*only),
+                _ => bail!(
+                    "{} is inside an element containing {} candidates; try 'associate': 'column' or 'preceding'",
+                    describe(child),
+                    found.len()
+                ),
+            }
+        })
+        .collect()
+}
+
+fn by_preceding(parents: &[ElementRef], children: &[ElementRef]) -> Result<Vec<usize>> {
+    let Some(first) = parents.first().or(children.first()) else {
+        return Ok(Vec::new());
+    };
+    let order = document_order(first);
+    let mut positions: Vec<(usize, usize)> = parents.iter().enumerate().map(|(index, parent)| (order[&parent.id()], index)).collect();
+    positions.sort_unstable();
+
+    children
+        .iter()
+        .map(|child| {
+            let before = positions.partition_point(|(position, _)| *position < order[&child.id()]);
+            match before.checked_sub(1) {
+                Some(last) => Ok(positions[last].1),
+                None => bail!("{} comes before every candidate", describe(child)),
+            }
+        })
+        .collect()
+}
+
+/// Where a node sits in a table: the table, the document position of its row and the
+/// columns its cell spans
+struct CellPosition {
+    table: NodeId,
+    row: usize,
+    columns: std::ops::Range<usize>,
+}
+
+fn by_column(parents: &[ElementRef], children: &[ElementRef]) -> Result<Vec<usize>> {
+    let Some(first) = parents.first().or(children.first()) else {
+        return Ok(Vec::new());
+    };
+    let order = document_order(first);
+    let locate = |element: &ElementRef| {
+        cell_position(*element, &order).ok_or_else(|| anyhow::anyhow!("{} is not inside a table cell", describe(element)))
+    };
+    let parent_cells = parents.iter().map(locate).collect::<Result<Vec<_>>>()?;
+
+    children
+        .iter()
+        .map(|child| {
+            let cell = locate(child)?;
+            let above = parent_cells
+                .iter()
+                .filter(|parent| parent.table == cell.table && parent.row < cell.row)
+                .map(|parent| parent.row)
+                .max();
+            let Some(row) = above else {
+                bail!("{} has no row of candidates above it in its table", describe(child));
+            };
+            parent_cells
+                .iter()
+                .position(|parent| parent.row == row && parent.columns.contains(&cell.columns.start))
+                .ok_or_else(|| anyhow::anyhow!("{} has no candidate in its column in the row above", describe(child)))
+        })
+        .collect()
+}
+
+fn cell_position(element: ElementRef, order: &HashMap<NodeId, usize>) -> Option<CellPosition> {
+    let is_cell = |element: &ElementRef| matches!(element.value().name(), "td" | "th");
+    let cell = std::iter::once(element)
+        .chain(element.ancestors().filter_map(ElementRef::wrap))
+        .find(is_cell)?;
+    let row = ElementRef::wrap(cell.parent()?).filter(|row| row.value().name() == "tr")?;
+    let table = row.ancestors().filter_map(ElementRef::wrap).find(|ancestor| ancestor.value().name() == "table")?;
+
+    let colspan = |cell: &ElementRef| cell.value().attr("colspan").and_then(|span| span.trim().parse().ok()).unwrap_or(1usize).max(1);
+    let start: usize = cell.prev_siblings().filter_map(ElementRef::wrap).filter(is_cell).map(|cell| colspan(&cell)).sum();
+    Some(CellPosition {
+        table: table.id(),
+        row: order[&row.id()],
+        columns: start..start + colspan(&cell),
+    })
+}
+
+/// Position of every node of the document `element` belongs to, in document order
+fn document_order(element: &ElementRef) -> HashMap<NodeId, usize> {
+    element.tree().root().descendants().enumerate().map(|(position, node)| (node.id(), position)).collect()
+}
+
+fn self_and_ancestors<'a>(element: &ElementRef<'a>) -> impl Iterator<Item = NodeId> + 'a {
+    std::iter::once(element.id()).chain(element.ancestors().map(|node| node.id()))
+}
+
+/// A short description of a node for error messages: its text, or its tag name
+fn describe(element: &ElementRef) -> String {
+    let text = collapsed_text(*element);
+    if text.is_empty() {
+        return format!("<{}>", element.value().name());
+    }
+    let mut short: String = text.chars().take(40).collect();
+    if short.len() < text.len() {
+        short.push('…');
+    }
+    format!("'{}'", short)
+}
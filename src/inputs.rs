@@ -0,0 +1,135 @@
+use std::fs;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow, bail};
+use clap::ValueEnum;
+use walkdir::WalkDir;
+
+/// Extensions of the files taken from a directory or glob-matched directory
+const HTML_EXTENSIONS: &[&str] = &["html", "htm", "xhtml"];
+
+/// How the results of several inputs are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// One JSON document: the result for a single file, an array of results otherwise
+    Json,
+    /// One result per line, written as each input is done
+    Ndjson,
+}
+
+/// The `--output` file, created when the first result is written to it, so a run that
+/// fails before producing anything leaves an existing file as it was
+pub struct OutputFile {
+    path: PathBuf,
+    file: Option<BufWriter<fs::File>>,
+}
+
+impl OutputFile {
+    pub fn new(path: &Path) -> Self {
+        OutputFile { path: path.to_path_buf(), file: None }
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => self.file.insert(BufWriter::new(fs::File::create(&self.path)?)),
+        };
+        file.write(bytes)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.as_mut().map_or(Ok(()), Write::flush)
+    }
+}
+
+/// A document to extract from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    Stdin,
+    File(PathBuf),
+}
+
+impl Input {
+    /// How the input is recorded in its result and named in messages
+    pub fn source(&self) -> String {
+        match self {
+            Input::Stdin => "-".to_string(),
+            Input::File(path) => path.display().to_string(),
+        }
+    }
+
+    /// The raw bytes of the document
+    pub fn read(&self) -> Result<Vec<u8>> {
+        match self {
+            Input::Stdin => {
+                let mut bytes = Vec::new();
+                std::io::stdin().read_to_end(&mut bytes).context("Failed to read HTML from stdin")?;
+                Ok(bytes)
+            }
+            Input::File(path) => fs::read(path).with_context(|| format!("Failed to read HTML file {:?}", path)),
+        }
+    }
+}
+
+/// The inputs named by `--html` values, in order: `-` for stdin, files, glob patterns
+/// and directories, searched recursively for HTML files in path order. A pattern or
+/// directory that yields no HTML file is an error, as is reading stdin twice.
+pub fn expand(patterns: &[String]) -> Result<Vec<Input>> {
+    let mut inputs = Vec::new();
+    for pattern in patterns {
+        if pattern == "-" {
+            if inputs.contains(&Input::Stdin) {
+                bail!("Standard input can only be given once as --html -");
+            }
+            inputs.push(Input::Stdin);
+            continue;
+        }
+        let path = Path::new(pattern);
+        let found = if path.is_dir() {
+            html_files_in(path)?
+        } else if path.exists() || !is_glob(pattern) {
+            vec![path.to_path_buf()]
+        } else {
+            let mut found = Vec::new();
+            for matched in glob::glob(pattern).map_err(|e| anyhow!("Invalid glob pattern '{}': {}", pattern, e))? {
+                let matched = matched.with_context(|| format!("Cannot read a path matching '{}'", pattern))?;
+                if matched.is_dir() {
+                    found.extend(html_files_in(&matched)?);
+                } else {
+                    found.push(matched);
+                }
+            }
+            found
+        };
+        if found.is_empty() {
+            bail!("No HTML files found for --html {}", pattern);
+        }
+        inputs.extend(found.into_iter().map(Input::File));
+    }
+    Ok(inputs)
+}
+
+/// Whether several results are written, as opposed to the single result of one file
+pub fn is_batch(patterns: &[String], inputs: &[Input]) -> bool {
+    inputs.len() > 1 || patterns.iter().any(|pattern| is_glob(pattern) || Path::new(pattern).is_dir())
+}
+
+fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+/// HTML files anywhere under a directory, sorted by path
+fn html_files_in(directory: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in WalkDir::new(directory).sort_by_file_name() {
+        let entry = entry.with_context(|| format!("Cannot read directory {:?}", directory))?;
+        let is_html = entry.path().extension().and_then(|extension| extension.to_str()).is_some_and(|extension| HTML_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str()));
+        if entry.file_type().is_file() && is_html {
+            files.push(entry.into_path());
+        }
+    }
+    Ok(files)
+}
@@ -0,0 +1,131 @@
+use scraper::{ElementRef, Selector};
+use serde_json::{Map, Value};
+
+use crate::content::collapsed_text;
+
+/// How a `key_value` rule should name and filter its keys
+pub struct KeyValueOptions<'a> {
+    /// Convert labels to snake_case keys ("Prev. Close:" -> "prev_close")
+    pub normalize_keys: bool,
+    /// Only keep these keys (compared after normalization)
+    pub keys: Option<&'a [String]>,
+}
+
+/// Collect label/value pairs found inside `container` into a JSON object.
+///
+/// Pairs come from `<dl>` term/description lists and table rows with exactly two
+/// cells. When the container has neither, its text is read line by line and
+/// "Label: value" lines are used instead. The first occurrence of a key wins.
+pub fn extract_key_values(container: ElementRef, options: &KeyValueOptions) -> Value {
+    let mut pairs = definition_list_pairs(container);
+    pairs.extend(two_cell_row_pairs(container));
+    if pairs.is_empty() {
+        pairs = text_line_pairs(container);
+    }
+
+    let allowed: Option<Vec<String>> = options
+        .keys
+        .map(|keys| keys.iter().map(|key| make_key(key, options.normalize_keys)).collect());
+
+    let mut result = Map::new();
+    for (label, value) in pairs {
+        let key = make_key(&label, options.normalize_keys);
+        if key.is_empty() || result.contains_key(&key) {
+            continue;
+        }
+        if let Some(allowed) = &allowed
+            && !allowed.contains(&key)
+        {
+            continue;
+        }
+        result.insert(key, value);
+    }
+    Value::Object(result)
+}
+
+fn make_key(label: &str, normalize: bool) -> String {
+    let label = label.trim().trim_end_matches([':', '：']).trim();
+    if normalize { snake_case(label) } else { label.to_string() }
+}
+
+/// Lowercase the label and join its alphanumeric runs with underscores
+fn snake_case(label: &str) -> String {
+    label
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+fn definition_list_pairs(container: ElementRef) -> Vec<(String, Value)> {
+    let dl_selector = Selector::parse("dl").unwrap();
+    let lists = std::iter::once(container)
+        .filter(|element| element.value().name() == "dl")
+        .chain(container.select(&dl_selector));
+
+    let mut pairs = Vec::new();
+    for list in lists {
+        let mut label: Option<String> = None;
+        let mut values: Vec<Value> = Vec::new();
+        for child in list.children().filter_map(ElementRef::wrap) {
+            match child.value().name() {
+                "dt" => {
+                    if let Some(label) = label.take() {
+                        pairs.push((label, collapse_values(std::mem::take(&mut values))));
+                    }
+                    label = Some(collapsed_text(child));
+                }
+                "dd" => values.push(Value::String(collapsed_text(child))),
+                _ => {}
+            }
+        }
+        if let Some(label) = label {
+            pairs.push((label, collapse_values(values)));
+        }
+    }
+    pairs
+}
+
+/// A term with several descriptions keeps all of them
+fn collapse_values(mut values: Vec<Value>) -> Value {
+    match values.len() {
+        0 => Value::Null,
+        1 => values.remove(0),
+        _ => Value::Array(values),
+    }
+}
+
+fn two_cell_row_pairs(container: ElementRef) -> Vec<(String, Value)> {
+    let row_selector = Selector::parse("tr").unwrap();
+    let mut pairs = Vec::new();
+    for row in container.select(&row_selector) {
+        let cells: Vec<ElementRef> = row
+            .children()
+            .filter_map(ElementRef::wrap)
+            .filter(|cell| matches!(cell.value().name(), "td" | "th"))
+            .collect();
+        if let [label, value] = cells.as_slice() {
+            let label = collapsed_text(*label);
+            if !label.is_empty() {
+                pairs.push((label, Value::String(collapsed_text(*value))));
+            }
+        }
+    }
+    pairs
+}
+
+fn text_line_pairs(container: ElementRef) -> Vec<(String, Value)> {
+    let text = crate::content::to_readable_text(container);
+    let mut pairs = Vec::new();
+    for line in text.lines() {
+        if let Some((label, value)) = line.split_once(':') {
+            let (label, value) = (label.trim(), value.trim());
+            // Long "labels" are sentences that happen to contain a colon
+            if !label.is_empty() && !value.is_empty() && label.split_whitespace().count() <= 6 {
+                pairs.push((label.to_string(), Value::String(value.to_string())));
+            }
+        }
+    }
+    pairs
+}
@@ -0,0 +1,137 @@
+use regex::Regex;
+use scraper::{ElementRef, Selector};
+use serde_json::{Map, Value};
+use url::Url;
+
+use crate::content::collapsed_text;
+use crate::urls::resolve_url;
+
+/// Options shared by the `links` and `images` harvesters
+pub struct HarvestOptions<'a> {
+    pub base_url: Option<&'a Url>,
+    pub strip_tracking: bool,
+    /// Only keep entries whose resolved URL matches
+    pub url_filter: Option<&'a Regex>,
+}
+
+/// Every distinct link (`<a href>`, `<area href>`) inside `scope`, in document order
+pub fn harvest_links(scope: ElementRef, options: &HarvestOptions) -> Vec<Value> {
+    let selector = Selector::parse("a[href], area[href]").unwrap();
+    let mut seen: Vec<String> = Vec::new();
+    let mut links = Vec::new();
+
+    for link in self_and_descendants(scope, &selector) {
+        let element = link.value();
+        let href = element.attr("href").unwrap_or("").trim();
+        if href.is_empty() || href.starts_with('#') || href.to_ascii_lowercase().starts_with("javascript:") {
+            continue;
+        }
+        let Some(url) = resolve_url(href, options.base_url, options.strip_tracking) else {
+            continue;
+        };
+        if !keep_url(&url, &mut seen, options) {
+            continue;
+        }
+
+        let mut entry = Map::new();
+        entry.insert("url".to_string(), Value::String(url.clone()));
+        entry.insert("text".to_string(), Value::String(collapsed_text(link)));
+        insert_attr(&mut entry, "title", element.attr("title"));
+        if let Some(rel) = element.attr("rel") {
+            let rel: Vec<Value> = rel.split_whitespace().map(|token| Value::String(token.to_lowercase())).collect();
+            entry.insert("rel".to_string(), Value::Array(rel));
+        }
+        entry.insert("internal".to_string(), Value::Bool(is_internal(href, &url, options.base_url)));
+        links.push(Value::Object(entry));
+    }
+    links
+}
+
+/// Every distinct image (`<img>`, using `data-src` for lazy-loaded images) inside `scope`
+pub fn harvest_images(scope: ElementRef, options: &HarvestOptions) -> Vec<Value> {
+    let selector = Selector::parse("img").unwrap();
+    let mut seen: Vec<String> = Vec::new();
+    let mut images = Vec::new();
+
+    for image in self_and_descendants(scope, &selector) {
+        let element = image.value();
+        let src = element
+            .attr("src")
+            .filter(|src| !src.trim().is_empty() && !src.trim_start().starts_with("data:"))
+            .or(element.attr("data-src"))
+            .unwrap_or("")
+            .trim();
+        let Some(url) = resolve_url(src, options.base_url, options.strip_tracking) else {
+            continue;
+        };
+        if !keep_url(&url, &mut seen, options) {
+            continue;
+        }
+
+        let mut entry = Map::new();
+        entry.insert("url".to_string(), Value::String(url.clone()));
+        insert_attr(&mut entry, "alt", element.attr("alt"));
+        insert_attr(&mut entry, "title", element.attr("title"));
+        for dimension in ["width", "height"] {
+            if let Some(size) = element.attr(dimension).and_then(|size| size.trim().trim_end_matches("px").parse::<u64>().ok()) {
+                entry.insert(dimension.to_string(), Value::Number(size.into()));
+            }
+        }
+        if let Some(srcset) = element.attr("srcset").or(element.attr("data-srcset")) {
+            entry.insert("srcset".to_string(), Value::Array(srcset_candidates(srcset, options)));
+        }
+        entry.insert("internal".to_string(), Value::Bool(is_internal(src, &url, options.base_url)));
+        images.push(Value::Object(entry));
+    }
+    images
+}
+
+fn self_and_descendants<'a>(scope: ElementRef<'a>, selector: &Selector) -> Vec<ElementRef<'a>> {
+    let mut elements: Vec<ElementRef<'a>> = Vec::new();
+    if selector.matches(&scope) {
+        elements.push(scope);
+    }
+    elements.extend(scope.select(selector));
+    elements
+}
+
+/// Apply the URL filter and drop URLs already harvested
+fn keep_url(url: &str, seen: &mut Vec<String>, options: &HarvestOptions) -> bool {
+    if options.url_filter.is_some_and(|filter| !filter.is_match(url)) || seen.iter().any(|s| s == url) {
+        return false;
+    }
+    seen.push(url.to_string());
+    true
+}
+
+/// A link is internal when it points at the base URL's host, or is relative and there
+/// is no base URL to compare against
+fn is_internal(raw: &str, resolved: &str, base_url: Option<&Url>) -> bool {
+    match (base_url, Url::parse(resolved)) {
+        (Some(base), Ok(url)) => url.host_str().is_some() && url.host_str() == base.host_str(),
+        _ => Url::parse(raw.trim()).is_err(),
+    }
+}
+
+/// Parse `srcset` into `{url, descriptor}` candidates ("img.png 2x", "img.png 300w")
+fn srcset_candidates(srcset: &str, options: &HarvestOptions) -> Vec<Value> {
+    srcset
+        .split(',')
+        .filter_map(|candidate| {
+            let mut parts = candidate.split_whitespace();
+            let url = resolve_url(parts.next()?, options.base_url, options.strip_tracking)?;
+            let mut entry = Map::new();
+            entry.insert("url".to_string(), Value::String(url));
+            if let Some(descriptor) = parts.next() {
+                entry.insert("descriptor".to_string(), Value::String(descriptor.to_string()));
+            }
+            Some(Value::Object(entry))
+        })
+        .collect()
+}
+
+fn insert_attr(entry: &mut Map<String, Value>, name: &str, value: Option<&str>) {
+    if let Some(value) = value.map(str::trim).filter(|value| !value.is_empty()) {
+        entry.insert(name.to_string(), Value::String(value.to_string()));
+    }
+}
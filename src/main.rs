@@ -1,143 +1,600 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::HashSet;
 use std::fs;
+use std::io::{self, Write};
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
+use std::time::Instant;
 use anyhow::{Result, Context};
-use scraper::{Html, Selector};
+use scraper::{ElementRef, Html, Selector};
 use encoding_rs::{Encoding, UTF_8};
-use chrono::Datelike;
-use regex;
+use url::Url;
+
+use compiled::Compiled;
+use config_format::ConfigFormat;
+use inputs::OutputFormat;
+use run_context::RunContext;
+
+mod compose;
+mod batch;
+mod compiled;
+mod config_format;
+mod content;
+mod dates;
+mod embedded_json;
+mod expect;
+mod expression;
+mod grouping;
+mod inputs;
+mod key_value;
+mod links;
+mod migrate;
+mod regex_extract;
+mod run_context;
+mod structured_data;
+mod tables;
+mod template;
+mod urls;
+mod validate;
+mod variables;
 
 #[derive(Parser)]
 #[command(name = "xpath-to-json")]
 #[command(about = "A CLI tool that processes HTML using XPath configurations to extract JSON data")]
+#[command(subcommand_negates_reqs = true)]
 struct Cli {
-    /// Path to the JSON configuration file
-    #[arg(long)]
-    xpath_config: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
     
-    /// Path to the HTML file to process
-    #[arg(long)]
-    html: PathBuf,
+    /// Path to the configuration file (JSON, YAML or TOML)
+    #[arg(long, required = true)]
+    xpath_config: Option<PathBuf>,
+    
+    /// Format of the configuration file (defaults to its extension, then JSON)
+    #[arg(long, value_enum)]
+    config_format: Option<ConfigFormat>,
+    
+    /// HTML to process: files, glob patterns, directories (searched recursively for .html/.htm files) or - for stdin
+    #[arg(long, required = true, num_args = 1..)]
+    html: Vec<String>,
     
     /// Path to the output file (optional - if not provided, output will be displayed)
     #[arg(long)]
     output: Option<PathBuf>,
+    
+    /// How results are written: JSON (an array when there are several inputs) or NDJSON, one result per line
+    #[arg(long, value_enum, default_value = "json")]
+    output_format: OutputFormat,
+    
+    /// Number of inputs processed at once (defaults to the number of CPUs)
+    #[arg(long)]
+    jobs: Option<NonZeroUsize>,
+    
+    /// Write each result as soon as it is done instead of in input order
+    #[arg(long)]
+    unordered: bool,
+    
+    /// Base URL used to resolve relative links (overrides the config's base_url)
+    #[arg(long)]
+    base_url: Option<String>,
+    
+    /// Fail when an output template uses a variable that was not extracted
+    #[arg(long)]
+    strict: bool,
+    
+    /// Directory to write each named output document to, as <name>.json
+    #[arg(long)]
+    output_dir: Option<PathBuf>,
+    
+    /// Use this time as "now" for template built-ins (RFC 3339, or YYYY-MM-DD[THH:MM[:SS]] in the config's timezone)
+    #[arg(long)]
+    now: Option<String>,
+    
+    /// Identifier of this run, available to templates as {runId} (generated when omitted)
+    #[arg(long)]
+    run_id: Option<String>,
+    
+    /// Set a variable declared in the config's `variables` (repeatable; the value is read as JSON when it parses as JSON)
+    #[arg(long = "var", value_name = "NAME=VALUE")]
+    vars: Vec<String>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Check configuration files for mistakes without running them
+    Validate {
+        /// Configuration files to check
+        #[arg(required = true)]
+        configs: Vec<PathBuf>,
+        /// Format of the configuration files (defaults to each file's extension, then JSON)
+        #[arg(long, value_enum)]
+        config_format: Option<ConfigFormat>,
+    },
+    /// Rewrite a configuration file in another format (comments are not kept)
+    ConvertConfig {
+        /// Configuration file to convert
+        input: PathBuf,
+        /// Format to write (defaults to the extension of --output)
+        #[arg(long, value_enum)]
+        to: Option<ConfigFormat>,
+        /// Format of the input (defaults to its extension, then JSON)
+        #[arg(long, value_enum)]
+        from: Option<ConfigFormat>,
+        /// File to write (prints to stdout when omitted)
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Rewrite configuration files in the current rule syntax and stamp them with the current version
+    Migrate {
+        /// Configuration files (or rule files) to migrate
+        #[arg(required = true)]
+        configs: Vec<PathBuf>,
+        /// Overwrite each file instead of printing the migrated configuration (comments are not kept)
+        #[arg(long)]
+        in_place: bool,
+        /// Format of the configuration files (defaults to each file's extension, then JSON)
+        #[arg(long, value_enum)]
+        config_format: Option<ConfigFormat>,
+    },
+    /// Print the JSON Schema of the configuration format
+    Schema,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 struct XPathConfig {
+    /// JSON Schema the file follows, for editors (ignored by the tool)
+    #[serde(rename = "$schema", skip_serializing_if = "Option::is_none")]
+    schema: Option<String>,
+    /// Version of the configuration format the file is written in (files without one are version 1)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<u64>,
+    /// Base configuration this one builds on, relative to this file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    extends: Option<String>,
+    /// Rule files (a list of rules, or an object with `rules` and `fragments`) whose rules come before this file's, relative to this file
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    include: Vec<String>,
+    /// Named rules, or parts of rules, that rules build on with `use`
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    fragments: serde_json::Map<String, Value>,
     /// Name of the configuration
     name: String,
     /// Description of what this configuration extracts
     description: Option<String>,
-    /// Sample of expected output format
-    output_sample: Option<Vec<serde_json::Value>>,
+    /// Base URL used to resolve relative links when the document has no <base href>
+    base_url: Option<String>,
+    /// Template for the output: an object, or an array of entries rendered in turn
+    output_sample: Option<serde_json::Value>,
+    /// Additional named output documents, each rendered from its own template
+    #[serde(default)]
+    outputs: serde_json::Map<String, Value>,
+    /// Fail on template variables that match no extracted field instead of leaving them as written
+    strict: Option<bool>,
+    /// IANA timezone for the template clock, e.g. "America/New_York" (defaults to UTC)
+    timezone: Option<String>,
+    /// Locale for month and weekday names, e.g. "es" or "pt_BR" (defaults to en)
+    locale: Option<String>,
+    /// Languages month and weekday names are read in (defaults to the locale's language and English)
+    locales: Option<Vec<String>>,
+    /// Values referenced as `$name` in XPaths and `{$name}` in templates, overridable with --var; null ones must be passed
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    variables: serde_json::Map<String, Value>,
     /// The XPath rules to execute
     rules: Vec<XPathRule>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 struct XPathRule {
-    /// Name/identifier for this rule
+    /// Fragment this rule builds on; the rule's own fields override the fragment's
+    #[serde(rename = "use", skip_serializing_if = "Option::is_none")]
+    use_fragment: Option<String>,
+    /// Name/identifier for this rule (defaults to the fragment it uses)
+    #[serde(default)]
     name: String,
-    /// The XPath expression to execute
+    /// What the field holds, for readers of the configuration
+    description: Option<String>,
+    /// A value the field typically holds, for readers of the configuration (checked against `expect` by validate)
+    example: Option<Value>,
+    /// Report an error when the rule extracts nothing (null, blank text or an empty list)
+    required: Option<bool>,
+    /// Warn when a value does not look like this (a regex `pattern` and/or a `type`)
+    expect: Option<expect::Expectation>,
+    /// The XPath expression to execute (empty selects the whole document or parent element)
+    #[serde(default)]
     xpath: String,
     /// What type of data to extract (text, attribute, html, etc.)
+    #[serde(default)]
     extract_type: ExtractType,
     /// Optional attribute name if extracting attributes
     attribute: Option<String>,
-    /// Whether this rule should be executed for each item from a previous rule
-    iterate_over: Option<String>,
-    /// Child rules to execute for each iteration
-    children: Option<Vec<XPathRule>>,
-    /// Child rules to execute for each iteration (alias for children)
-    #[serde(alias = "fields")]
+    /// Remove tracking query parameters (utm_*, fbclid, ...) from extracted URLs
+    strip_tracking: Option<bool>,
+    /// Tags kept by `sanitized_html` (replaces the default allow-list)
+    allowed_tags: Option<Vec<String>>,
+    /// Attributes kept on any tag by `sanitized_html` (replaces the default allow-list)
+    allowed_attributes: Option<Vec<String>>,
+    /// For `table`: 1-based column whose cells become keys of a label/value object
+    key_column: Option<usize>,
+    /// For `table`: number of leading header rows, overriding <thead>/<th> detection
+    header_rows: Option<usize>,
+    /// For `key_value`: convert labels to snake_case keys (default true)
+    normalize_keys: Option<bool>,
+    /// For `key_value`: only keep these keys
+    keys: Option<Vec<String>>,
+    /// For `json_ld`, `microdata` and `rdfa`: only keep entities of this @type
+    schema_type: Option<String>,
+    /// For `links` and `images`: only keep entries whose resolved URL matches this regex
+    url_pattern: Option<String>,
+    /// Regex for `regex` rules, or locating the JSON literal for `script_json`
+    pattern: Option<String>,
+    /// For `regex`: capture group (index or name) to return
+    group: Option<regex_extract::CaptureGroup>,
+    /// For `regex`: return every match instead of only the first
+    all_matches: Option<bool>,
+    /// For `regex`: what the pattern runs against (text, html or document)
+    regex_source: Option<RegexSource>,
+    /// For `script_json`: name the JSON literal is assigned to, e.g. `window.__INITIAL_STATE__`
+    assignment: Option<String>,
+    /// JSONPath selecting into the JSON of a `script_json` rule (or of its parent, for child rules)
+    jsonpath: Option<String>,
+    /// For `computed`: expression over previously extracted fields
+    expression: Option<String>,
+    /// Only emit this field when the condition holds
+    when: Option<Condition>,
+    /// Merge the keys of an object result into the extracted data instead of nesting it under the rule name
+    merge: Option<bool>,
+    /// Child rules evaluated inside each matched element, giving one object per element
     fields: Option<Vec<XPathRule>>,
-    /// For each item rule (new structure)
-    #[serde(rename = "for-each-item")]
-    for_each_item: Option<Box<XPathRule>>,
-    /// Map item rule (new structure)
-    #[serde(rename = "map-item")]
-    map_item: Option<Box<XPathRule>>,
+    /// Rule whose matches are grouped under the matches of this rule (see Grouping)
+    items: Option<Box<XPathRule>>,
+    /// For `items` rules: how each matched node is attached to a node of the rule above
+    /// it (container, column or preceding)
+    associate: Option<grouping::Association>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema)]
 enum ExtractType {
+    #[default]
     #[serde(rename = "text")]
     Text,
     #[serde(rename = "attribute")]
     Attribute,
     #[serde(rename = "html")]
     Html,
+    #[serde(rename = "inner_html")]
+    InnerHtml,
+    #[serde(rename = "outer_html")]
+    OuterHtml,
+    #[serde(rename = "sanitized_html")]
+    SanitizedHtml,
     #[serde(rename = "count")]
     Count,
+    #[serde(rename = "exists")]
+    Exists,
     #[serde(rename = "object")]
     Object,
+    #[serde(rename = "url")]
+    Url,
+    #[serde(rename = "markdown")]
+    Markdown,
+    #[serde(rename = "readable_text")]
+    ReadableText,
+    #[serde(rename = "table")]
+    Table,
+    #[serde(rename = "key_value")]
+    KeyValue,
+    #[serde(rename = "json_ld")]
+    JsonLd,
+    #[serde(rename = "microdata")]
+    Microdata,
+    #[serde(rename = "rdfa")]
+    Rdfa,
+    #[serde(rename = "opengraph")]
+    OpenGraph,
+    #[serde(rename = "meta")]
+    Meta,
+    #[serde(rename = "script_json")]
+    ScriptJson,
+    #[serde(rename = "regex")]
+    Regex,
+    #[serde(rename = "links")]
+    Links,
+    #[serde(rename = "images")]
+    Images,
+    #[serde(rename = "computed")]
+    Computed,
+}
+
+/// Condition deciding whether a rule's field is emitted at all
+#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+struct Condition {
+    /// XPath that must match at least one element (relative to the parent element for child rules)
+    xpath: Option<String>,
+    /// Previously extracted field to test (a sibling field for child rules); on its own it must be non-empty
+    field: Option<String>,
+    /// The field must equal this value
+    equals: Option<Value>,
+    /// The field must not equal this value
+    not_equals: Option<Value>,
+    /// The field must match this regex
+    matches: Option<String>,
+    /// Invert the whole condition
+    #[serde(default)]
+    not: bool,
+}
+
+/// What a `regex` rule matches against
+#[derive(Debug, Default, Deserialize, Serialize, JsonSchema)]
+enum RegexSource {
+    /// The matched element's text content
+    #[default]
+    #[serde(rename = "text")]
+    Text,
+    /// The matched element's outer HTML, including comments and inline scripts
+    #[serde(rename = "html")]
+    Html,
+    /// The whole document source as read from disk
+    #[serde(rename = "document")]
+    Document,
 }
 
 #[derive(Debug, Serialize)]
 struct ExtractionResult {
     /// The name of the configuration
     config_name: String,
+    /// The input the data was extracted from, as given on the command line (- for stdin)
+    source: String,
     /// The extracted data
     data: Value,
     /// Any errors that occurred during extraction
     errors: Vec<String>,
+    /// Values that do not look as their rules expect
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<String>,
+    /// Named output documents rendered from the config's `outputs`
+    #[serde(skip_serializing_if = "serde_json::Map::is_empty")]
+    outputs: serde_json::Map<String, Value>,
 }
 
+impl ExtractionResult {
+    /// The result of an input that could not be processed at all
+    fn failed(config: &XPathConfig, source: String, error: &anyhow::Error) -> Self {
+        ExtractionResult {
+            config_name: config.name.clone(),
+            source,
+            data: Value::Null,
+            errors: vec![format!("{:#}", error)],
+            warnings: Vec::new(),
+            outputs: serde_json::Map::new(),
+        }
+    }
+}
+
+/// State shared by every rule evaluated against a single document
+struct ExtractionContext<'a> {
+    /// The parsed HTML document
+    document: &'a Html,
+    /// The document source as read, for rules that match against raw HTML
+    source: &'a str,
+    /// Base URL used to resolve relative links for `url` extractions
+    base_url: Option<Url>,
+    /// Values of the config's variables, bound to `$name` in XPaths
+    variables: &'a serde_json::Map<String, Value>,
+    /// The rules' selectors, regexes and expressions, compiled once for the run
+    compiled: &'a Compiled,
+}
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
     
+    match &cli.command {
+        Some(Command::Validate { configs, config_format }) => {
+            if !validate::validate_files(configs, *config_format)? {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Some(Command::ConvertConfig { input, to, from, output }) => {
+            let Some(to) = to.or_else(|| output.as_deref().and_then(ConfigFormat::from_extension)) else {
+                return Err(anyhow::anyhow!("Pass --to, or an --output file whose extension names the format"));
+            };
+            let converted = config_format::convert(input, *from, to)?;
+            match output {
+                Some(path) => {
+                    fs::write(path, converted).with_context(|| format!("Failed to write {:?}", path))?;
+                    println!("Configuration written to {:?}", path);
+                }
+                None => print!("{}", converted),
+            }
+            Ok(())
+        }
+        Some(Command::Migrate { configs, in_place, config_format }) => {
+            if configs.len() > 1 && !in_place {
+                return Err(anyhow::anyhow!("Pass --in-place to migrate several files at once"));
+            }
+            for path in configs {
+                let format = ConfigFormat::resolve(*config_format, path);
+                let mut config = config_format::read(path, Some(format))?;
+                let changes = migrate::upgrade(&mut config).with_context(|| format!("Cannot migrate {:?}", path))?;
+                migrate::stamp_version(&mut config);
+                for change in &changes {
+                    eprintln!("{}: {}", path.display(), change);
+                }
+                let migrated = format.write(&config)?;
+                if *in_place {
+                    fs::write(path, migrated).with_context(|| format!("Failed to write {:?}", path))?;
+                    eprintln!("{}: migrated to version {}", path.display(), migrate::CURRENT_VERSION);
+                } else {
+                    print!("{}", migrated);
+                }
+            }
+            Ok(())
+        }
+        Some(Command::Schema) => {
+            let schema = schemars::schema_for!(XPathConfig);
+            println!("{}", serde_json::to_string_pretty(&schema).context("Failed to serialize schema")?);
+            Ok(())
+        }
+        None => extract(cli),
+    }
+}
+
+fn extract(cli: Cli) -> Result<()> {
+    let Some(config_path) = &cli.xpath_config else {
+        return Err(anyhow::anyhow!("--xpath-config and --html are required"));
+    };
+    let inputs = inputs::expand(&cli.html)?;
+    let batch = inputs::is_batch(&cli.html, &inputs);
+    if batch && cli.output_dir.is_some() {
+        return Err(anyhow::anyhow!("--output-dir can only be used with a single --html file"));
+    }
+    
     // Read and parse the configuration
-    let config_content = fs::read_to_string(&cli.xpath_config)
-        .context("Failed to read configuration file")?;
-    let config: XPathConfig = serde_json::from_str(&config_content)
-        .context("Failed to parse configuration JSON")?;
+    let (config, deprecations) = compose::load(config_path, cli.config_format)?;
+    for deprecation in &deprecations {
+        eprintln!("Warning: {}", deprecation);
+    }
+    let config: XPathConfig = serde_path_to_error::deserialize(config)
+        .context("Invalid configuration")?;
     
-    // Read the HTML content with encoding detection
-    let html_content = read_html_file(&cli.html)?;
+    // Everything templates can know about this run, fixed once up front
+    let timezone = run_context::parse_timezone(config.timezone.as_deref().unwrap_or("UTC"))?;
+    let now = run_context::resolve_now(cli.now.as_deref(), timezone)?;
+    let locale = config.locale.as_deref().unwrap_or("en");
+    let mut languages = match &config.locales {
+        Some(locales) => locales.iter().map(|locale| dates::language_of(locale)).collect::<Result<Vec<_>>>()?,
+        None => dates::language_of(locale).into_iter().chain(Some("en".to_string())).collect(),
+    };
+    // "en_US" and "en_GB" are both "en", wherever they appear in the list
+    let mut seen = HashSet::new();
+    languages.retain(|language| seen.insert(language.clone()));
+    let run = RunContext {
+        locale: run_context::parse_locale(locale)?,
+        languages,
+        source_file: String::new(),
+        config_name: config.name.clone(),
+        run_id: cli.run_id.clone().unwrap_or_else(|| run_context::generate_run_id(&now)),
+        strict: cli.strict || config.strict.unwrap_or(false),
+        base_url: cli.base_url.clone(),
+        variables: variables::resolve(&config.variables, &cli.vars)?,
+        now,
+    };
     
-    // Process the HTML with the configuration
-    let result = process_html(&config, &html_content)?;
+    let compiled = Compiled::new(&config.rules, &run.variables);
+    let mut writer: Box<dyn Write> = match &cli.output {
+        Some(path) => Box::new(inputs::OutputFile::new(path)),
+        None => Box::new(io::stdout().lock()),
+    };
+    let started = Instant::now();
+    let jobs = cli.jobs.map_or_else(batch::default_jobs, NonZeroUsize::get);
+    let (mut with_errors, mut failed) = (0, 0);
+    let mut results = Vec::new();
+    batch::run(&inputs, jobs, !cli.unordered, |input| {
+        // Process the HTML with the configuration; the run is the same for every input
+        let run = RunContext { source_file: input.source(), ..run.clone() };
+        let html_content = decode_html(&input.read()?);
+        process_html(&config, &html_content, &run, &compiled)
+    }, |input, outcome| {
+        let result = match outcome {
+            Ok(result) => {
+                if !result.errors.is_empty() {
+                    with_errors += 1;
+                }
+                result
+            }
+            // One input failing does not stop the others; it gets a result holding the error
+            Err(e) if batch => {
+                eprintln!("Error: {}: {:#}", input.source(), e);
+                failed += 1;
+                ExtractionResult::failed(&config, input.source(), &e)
+            }
+            Err(e) => return Err(e),
+        };
+        if cli.output_format == OutputFormat::Ndjson {
+            let line = serde_json::to_string(&result)
+                .context("Failed to serialize result to JSON")?;
+            writeln!(writer, "{}", line).context("Failed to write output")?;
+            // A single result is still needed for --output-dir
+            if !batch {
+                results.push(result);
+            }
+        } else {
+            results.push(result);
+        }
+        Ok(())
+    })?;
     
-    // Output the result
-    let output_json = serde_json::to_string_pretty(&result)
-        .context("Failed to serialize result to JSON")?;
+    if cli.output_format == OutputFormat::Json {
+        let output_json = if batch {
+            serde_json::to_string_pretty(&results)
+        } else {
+            serde_json::to_string_pretty(&results[0])
+        }.context("Failed to serialize result to JSON")?;
+        writeln!(writer, "{}", output_json).context("Failed to write output")?;
+    }
+    writer.flush().context("Failed to write output")?;
     
-    if let Some(output_path) = cli.output {
-        fs::write(&output_path, output_json)
-            .context("Failed to write output file")?;
+    if batch {
+        eprintln!(
+            "Processed {} inputs in {:.2}s with {} worker(s): {} ok, {} with errors, {} failed",
+            inputs.len(),
+            started.elapsed().as_secs_f64(),
+            jobs.min(inputs.len()),
+            inputs.len() - with_errors - failed,
+            with_errors,
+            failed
+        );
+    }
+    
+    if let (Some(output_dir), [result]) = (&cli.output_dir, results.as_slice()) {
+        fs::create_dir_all(output_dir)
+            .context("Failed to create output directory")?;
+        for (name, document) in &result.outputs {
+            if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+                return Err(anyhow::anyhow!("Output name '{}' cannot be used as a file name", name));
+            }
+            let path = output_dir.join(format!("{}.json", name));
+            let json = serde_json::to_string_pretty(document)
+                .context("Failed to serialize output document to JSON")?;
+            fs::write(&path, json)
+                .with_context(|| format!("Failed to write output document {:?}", path))?;
+            eprintln!("Output '{}' written to {:?}", name, path);
+        }
+    }
+    
+    if let Some(output_path) = &cli.output {
         println!("Results written to {:?}", output_path);
-    } else {
-        println!("{}", output_json);
+    }
+    if failed > 0 {
+        return Err(anyhow::anyhow!("{} of {} inputs could not be processed", failed, inputs.len()));
     }
     
     Ok(())
 }
 
-fn read_html_file(path: &PathBuf) -> Result<String> {
-    // Read the file as bytes first
-    let bytes = fs::read(path)
-        .context("Failed to read HTML file")?;
-    
+fn decode_html(bytes: &[u8]) -> String {
     // Try to detect encoding from HTML meta tag
-    let html_str = String::from_utf8_lossy(&bytes);
+    let html_str = String::from_utf8_lossy(bytes);
     let encoding = detect_encoding(&html_str);
     
     // Decode using the detected encoding
-    let (decoded, _, _) = encoding.decode(&bytes);
+    let (decoded, _, _) = encoding.decode(bytes);
     
-    Ok(decoded.to_string())
+    decoded.to_string()
 }
 
 fn detect_encoding(html: &str) -> &'static Encoding {
     // Look for charset in meta tag
     if let Some(charset_start) = html.find("charset=") {
         let charset_value = &html[charset_start + 8..];
-        let charset_end = charset_value.find(|c: char| c == '"' || c == '>' || c == ' ').unwrap_or(charset_value.len());
+        let charset_end = charset_value.find(['"', '>', ' ']).unwrap_or(charset_value.len());
         let charset = charset_value[..charset_end].trim().to_lowercase();
         
         match charset.as_str() {
@@ -156,62 +613,50 @@ fn detect_encoding(html: &str) -> &'static Encoding {
     UTF_8
 }
 
-fn process_html(config: &XPathConfig, html_content: &str) -> Result<ExtractionResult> {
+fn process_html(config: &XPathConfig, html_content: &str, run: &RunContext, compiled: &Compiled) -> Result<ExtractionResult> {
     let mut errors = Vec::new();
+    let mut findings = expect::Findings::default();
     let mut raw_data = serde_json::Map::new();
     
     // Parse HTML
     let document = Html::parse_document(html_content);
     
+    // The CLI base URL wins over the config one; a <base href> in the document wins over both
+    let external_base_url = match run.base_url.as_deref().or(config.base_url.as_deref()) {
+        Some(url) => Some(Url::parse(url).with_context(|| format!("Invalid base URL '{}'", url))?),
+        None => None,
+    };
+    let ctx = ExtractionContext {
+        document: &document,
+        source: html_content,
+        base_url: urls::document_base_url(&document, external_base_url),
+        variables: &run.variables,
+        compiled,
+    };
+    
     // Process each rule to get raw data
     for rule in &config.rules {
-        match process_rule(&document, rule) {
+        // Computed fields are derived once everything else has been extracted
+        if let ExtractType::Computed = rule.extract_type {
+            continue;
+        }
+        if let Some(condition) = &rule.when {
+            match condition_holds(&ctx, None, condition, &raw_data) {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(e) => {
+                    errors.push(format!("Error evaluating condition of rule '{}': {}", rule.name, e));
+                    continue;
+                }
+            }
+        }
+        
+        match process_rule(&ctx, rule) {
             Ok(value) => {
-                // Handle nested structure for months -> days -> stocks
-                if rule.name == "months" && rule.for_each_item.is_some() {
-                    // Process the months XPath directly to get the actual month names
-                    let css_selector = xpath_to_css_selector(&rule.xpath)?;
-                    let selector = scraper::Selector::parse(&css_selector)
-                        .map_err(|e| anyhow::anyhow!("Invalid CSS selector: {}", e))?;
-                    let mut months_results = Vec::new();
-                    for element in document.select(&selector) {
-                        let text = element.text().collect::<String>().trim().to_string();
-                        if !text.is_empty() {
-                            months_results.push(Value::String(text));
-                        }
-                    }
-                    let months_result = if months_results.len() == 1 {
-                        months_results.into_iter().next().unwrap_or(Value::Null)
-                    } else {
-                        Value::Array(months_results)
-                    };
-                    raw_data.insert("months".to_string(), months_result);
-
-                    // Process the days for each month
-                    if let Some(for_each_item) = &rule.for_each_item {
-                        let days_result = process_rule(&document, for_each_item)?;
-                        raw_data.insert("days".to_string(), days_result.clone());
-
-                        // Process items for each day using find_items_for_day
-                        if let Some(_map_item) = &for_each_item.map_item {
-                            // Get the days array
-                            let days_array = match &days_result {
-                                Value::Array(arr) => arr,
-                                _ => return Err(anyhow::anyhow!("Days result is not an array")),
-                            };
-
-                            // Create a map of day -> items using find_items_for_day
-                            let mut day_items_map = serde_json::Map::new();
-                            for day_value in days_array {
-                                if let Some(day_str) = day_value.as_str() {
-                                    let day_items = find_items_for_day(&document, day_str)?;
-                                    day_items_map.insert(day_str.to_string(), Value::Array(day_items));
-                                }
-                            }
-                            
-                            // Store the day-items mapping
-                            raw_data.insert("day_items".to_string(), Value::Object(day_items_map));
-                        }
+                expect::check_rule(rule, Some(&value), &rule.name, run, compiled, &mut findings);
+                if rule.merge.unwrap_or(false) && value.is_object() {
+                    if let Value::Object(fields) = value {
+                        raw_data.extend(fields);
                     }
                 } else {
                     raw_data.insert(rule.name.clone(), value);
@@ -225,447 +670,64 @@ fn process_html(config: &XPathConfig, html_content: &str) -> Result<ExtractionRe
         }
     }
     
+    // Derive computed fields from the extracted data, in rule order
+    for rule in config.rules.iter().filter(|rule| matches!(rule.extract_type, ExtractType::Computed)) {
+        let computed = match &rule.when {
+            Some(condition) => condition_holds(&ctx, None, condition, &raw_data)
+                .and_then(|holds| if holds { evaluate_computed(&ctx, rule, &raw_data).map(Some) } else { Ok(None) }),
+            None => evaluate_computed(&ctx, rule, &raw_data).map(Some),
+        };
+        match computed {
+            Ok(Some(value)) => {
+                expect::check_rule(rule, Some(&value), &rule.name, run, compiled, &mut findings);
+                raw_data.insert(rule.name.clone(), value);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                errors.push(format!("Error computing field '{}': {}", rule.name, e));
+                raw_data.insert(rule.name.clone(), Value::Null);
+            }
+        }
+    }
+    
+    errors.extend(findings.errors);
+    
     // Generate structured output based on the configuration
+    let mut outputs = serde_json::Map::new();
+    for (name, output_template) in &config.outputs {
+        let document = template::render(output_template, &raw_data, run)
+            .with_context(|| format!("Failed to render output '{}'", name))?;
+        outputs.insert(name.clone(), document);
+    }
     let structured_data = if let Some(output_sample) = &config.output_sample {
-        generate_structured_output(&raw_data, output_sample, &document)?
+        template::render(output_sample, &raw_data, run)?
     } else {
         Value::Object(raw_data)
     };
     
     Ok(ExtractionResult {
         config_name: config.name.clone(),
+        source: run.source_file.clone(),
         data: structured_data,
         errors,
+        warnings: findings.warnings,
+        outputs,
     })
 }
 
-fn generate_structured_output(raw_data: &serde_json::Map<String, Value>, output_sample: &[serde_json::Value], document: &Html) -> Result<Value> {
-    // Process the hierarchical template structure
-    let result = process_hierarchical_template(&output_sample[0], raw_data, document)?;
-    Ok(Value::Array(vec![result]))
-}
-
-fn process_hierarchical_template(template: &Value, raw_data: &serde_json::Map<String, Value>, document: &Html) -> Result<Value> {
-    match template {
-        Value::Object(obj) => {
-            let mut result = serde_json::Map::new();
-            for (key, value) in obj {
-                // Special handling for months variable - process BEFORE calling process_template_variable
-                if key == "{months}" {
-                    // Get the actual month names from the months array
-                    if let Some(months_array) = raw_data.get("months").and_then(|v| v.as_array()) {
-                        // Sort months in chronological order
-                        let mut sorted_months = months_array.clone();
-                        sorted_months.sort_by(|a, b| {
-                            let month_order = ["January", "February", "March", "April", "May", "June", 
-                                             "July", "August", "September", "October", "November", "December"];
-                            
-                            let a_name = a.as_str().and_then(|s| s.split_whitespace().next()).unwrap_or("");
-                            let b_name = b.as_str().and_then(|s| s.split_whitespace().next()).unwrap_or("");
-                            
-                            let a_index = month_order.iter().position(|&m| m == a_name).unwrap_or(12);
-                            let b_index = month_order.iter().position(|&m| m == b_name).unwrap_or(12);
-                            
-                            a_index.cmp(&b_index)
-                        });
-                        
-                        // Process each month found in the HTML in chronological order
-                        let mut month_results = Vec::new();
-                        for month_value in sorted_months {
-                            if let Some(month_str) = month_value.as_str() {
-                                // Extract month name from string like "October 2025     — Ex-Dividend Calendar"
-                                let month_name = month_str.split_whitespace().next().unwrap_or("October");
-                                let full_month_name = format!("{} 2025", month_name);
-                                
-                                // Create month-specific raw_data for this month
-                                let mut month_raw_data = raw_data.clone();
-                                
-                                // Process days for this specific month
-                                if let Some(day_items_obj) = raw_data.get("day_items").and_then(|v| v.as_object()) {
-                                    // Filter day_items to only include items for this month
-                                    let mut month_day_items = serde_json::Map::new();
-                                    
-                                    // For each day, get items using the month-aware function
-                                    for (day_key, _) in day_items_obj {
-                                        if let Ok(month_items) = find_items_for_day_in_month(&document, day_key, Some(&full_month_name)) {
-                                            month_day_items.insert(day_key.clone(), Value::Array(month_items));
-                                        }
-                                    }
-                                    
-                                    month_raw_data.insert("day_items".to_string(), Value::Object(month_day_items));
-                                }
-                                
-                                let processed_value = process_hierarchical_template(value, &month_raw_data, document)?;
-                                month_results.push((month_name.to_string(), processed_value));
-                            }
-                        }
-                        
-                        // Insert months in the correct order
-                        for (month_name, processed_value) in month_results {
-                            result.insert(month_name, processed_value);
-                        }
-                        continue;
-                    }
-                }
-                
-                // Special handling for numbered day variables like {days0}, {days1}, {days0-30}
-                if key.starts_with("{days") && key.ends_with("}") {
-                    let rule_name = &key[1..key.len()-1]; // Remove { and }
-                    if rule_name.starts_with("days") && rule_name.len() > 4 {
-                        // Handle range syntax like "days0-30"
-                        if rule_name.contains("-") {
-                            if let Some((start_str, end_str)) = rule_name[4..].split_once("-") {
-                                if let (Ok(start), Ok(end)) = (start_str.parse::<usize>(), end_str.parse::<usize>()) {
-                                    let processed_value = process_day_range_with_items(raw_data, start, end)?;
-                                    // Don't use processed_key here, iterate through the result
-                                    if let Value::Object(day_map) = processed_value {
-                                        for (day_num, items) in day_map {
-                                            result.insert(day_num, items);
-                                        }
-                                    }
-                                    continue;
-                                }
-                            }
-                        } else {
-                            // Handle single day syntax like "days0"
-                            if let Ok(day_index) = rule_name[4..].parse::<usize>() {
-                                let processed_value = process_numbered_days_with_items(raw_data, day_index)?;
-                                let processed_key = process_template_variable(key, raw_data)?;
-                                result.insert(processed_key, processed_value);
-                                continue;
-                            }
-                        }
-                    }
-                }
-                
-                    let processed_key = process_template_variable(key, raw_data)?;
-                    let processed_value = process_hierarchical_template(value, raw_data, document)?;
-                    result.insert(processed_key, processed_value);
-            }
-            Ok(Value::Object(result))
-        },
-        Value::Array(arr) => {
-            // Special handling for days array - group items by day
-            if arr.len() == 1 && arr[0].as_str() == Some("{items}") {
-                return process_days_with_items(raw_data);
-            }
-            
-            // Special handling for paired data like {"{history-date}": "{history-value}"}
-            if arr.len() == 1 {
-                if let Some(obj) = arr[0].as_object() {
-                    if obj.len() == 1 {
-                        let (key, value) = obj.iter().next().unwrap();
-                        if key.starts_with('{') && key.ends_with('}') && 
-                           value.as_str().map_or(false, |s| s.starts_with('{') && s.ends_with('}')) {
-                            return process_paired_data(key, value.as_str().unwrap(), raw_data);
-                        }
-                    }
-                }
-            }
-            
-            let mut result = Vec::new();
-            for item in arr {
-                let processed_item = process_hierarchical_template(item, raw_data, document)?;
-                result.push(processed_item);
-            }
-            Ok(Value::Array(result))
-        },
-        Value::String(s) => {
-            if s.starts_with('{') && s.ends_with('}') {
-                let rule_name = &s[1..s.len()-1]; // Remove { and }
-                
-                // Handle special syntactic sugar variables
-                if rule_name == "currentYear" {
-                    return Ok(Value::String(chrono::Utc::now().year().to_string()));
-                } else if rule_name == "currentMonth" {
-                    return Ok(Value::String(chrono::Utc::now().month().to_string()));
-                } else if rule_name == "currentDay" {
-                    return Ok(Value::String(chrono::Utc::now().day().to_string()));
-                } else if rule_name == "currentDate" {
-                    return Ok(Value::String(chrono::Utc::now().format("%Y-%m-%d").to_string()));
-                } else if rule_name.starts_with("days") && rule_name.len() > 4 {
-                    // Handle numbered day variables like {days0}, {days1}, etc.
-                    if let Ok(day_index) = rule_name[4..].parse::<usize>() {
-                        return process_numbered_days_with_items(raw_data, day_index);
-                    }
-                } else {
-                    // Handle regular rule variables
-                    if let Some(raw_value) = raw_data.get(rule_name) {
-                        return Ok(raw_value.clone());
-                    }
-                }
-            }
-            Ok(Value::String(s.clone()))
-        },
-        _ => Ok(template.clone())
-    }
-}
-
-fn process_paired_data(key_template: &str, value_template: &str, raw_data: &serde_json::Map<String, Value>) -> Result<Value> {
-    // Extract rule names from templates
-    let key_rule = &key_template[1..key_template.len()-1]; // Remove { and }
-    let value_rule = &value_template[1..value_template.len()-1]; // Remove { and }
-    
-    // Get the arrays for both rules
-    let empty_vec = Vec::new();
-    let key_array = raw_data.get(key_rule).and_then(|v| v.as_array()).unwrap_or(&empty_vec);
-    let value_array = raw_data.get(value_rule).and_then(|v| v.as_array()).unwrap_or(&empty_vec);
-    
-    let mut result = Vec::new();
-    
-    // Create pairs up to the minimum length
-    let min_len = key_array.len().min(value_array.len());
-    for i in 0..min_len {
-        if let (Some(key_val), Some(value_val)) = (key_array.get(i), value_array.get(i)) {
-            if let (Some(key_str), Some(value_str)) = (key_val.as_str(), value_val.as_str()) {
-                let mut pair = serde_json::Map::new();
-                pair.insert(key_str.trim().to_string(), Value::String(value_str.trim().to_string()));
-                result.push(Value::Object(pair));
-            }
-        }
-    }
-    
-    Ok(Value::Array(result))
-}
-
-fn process_days_with_items(raw_data: &serde_json::Map<String, Value>) -> Result<Value> {
-    // Get days and items arrays (generic field names)
-    let empty_vec = vec![];
-    let days = raw_data.get("days").and_then(|v| v.as_array()).unwrap_or(&empty_vec);
-    let items = raw_data.get("items").and_then(|v| v.as_array()).unwrap_or(&empty_vec);
-    
-    // Group items by day
-    let mut result = serde_json::Map::new();
-    
-    // Create a map of day -> items
-    let mut day_items_map: std::collections::HashMap<String, Vec<Value>> = std::collections::HashMap::new();
-    
-    // For each item, try to find which day it belongs to
-    // This is a simplified approach - in reality, you might need more complex logic
-    // to properly associate items with specific days based on the HTML structure
-    
-    for (i, item) in items.iter().enumerate() {
-        // Use modulo to distribute items across available days
-        if !days.is_empty() {
-            let day_index = i % days.len();
-            if let Some(day_value) = days.get(day_index) {
-                if let Some(day_str) = day_value.as_str() {
-                    let day_key = day_str.trim().to_string();
-                    day_items_map.entry(day_key).or_insert_with(Vec::new).push(item.clone());
-                }
-            }
-        }
-    }
-    
-    // Convert the map to the result structure
-    for (day, items_for_day) in day_items_map {
-        result.insert(day, Value::Array(items_for_day));
-    }
-    
-    Ok(Value::Object(result))
-}
-
-fn process_numbered_days_with_items(raw_data: &serde_json::Map<String, Value>, day_index: usize) -> Result<Value> {
-    // Get days and items arrays (generic field names)
-    let empty_vec = vec![];
-    let days = raw_data.get("days").and_then(|v| v.as_array()).unwrap_or(&empty_vec);
-    let items = raw_data.get("items").and_then(|v| v.as_array()).unwrap_or(&empty_vec);
-    
-    // Get the specific day for this index
-    let day_value = if day_index < days.len() {
-        days.get(day_index)
-    } else {
-        None
-    };
-    
-    if let Some(day) = day_value {
-        if let Some(day_str) = day.as_str() {
-            let _day_key = day_str.trim().to_string();
-            
-            // Get items for this specific day
-            // This is a simplified approach - you might need more complex logic
-            // to properly associate items with specific days based on the HTML structure
-            let mut items_for_day = Vec::new();
-            
-            // For now, distribute items evenly across days
-            if !days.is_empty() && !items.is_empty() {
-                let items_per_day = items.len() / days.len();
-                let start_index = day_index * items_per_day;
-                let end_index = if day_index == days.len() - 1 {
-                    items.len()
-                } else {
-                    start_index + items_per_day
-                };
-                
-                for i in start_index..end_index {
-                    if let Some(item) = items.get(i) {
-                        items_for_day.push(item.clone());
-                    }
-                }
-            }
-            
-            return Ok(Value::Array(items_for_day));
-        }
-    }
-    
-    Ok(Value::Array(vec![]))
-}
-
-fn process_day_range_with_items(raw_data: &serde_json::Map<String, Value>, start_day: usize, end_day: usize) -> Result<Value> {
-    let mut result = serde_json::Map::new();
-    
-    // Use the new day_items mapping if available
-    if let Some(day_items_obj) = raw_data.get("day_items")
-        .and_then(|v| v.as_object()) {
-        
-        for i in start_day..=end_day {
-            // Skip day 0 as requested by user
-            if i == 0 {
-                continue;
-            }
-            let day_key = i.to_string();
-            if let Some(day_items) = day_items_obj.get(&day_key) {
-                result.insert(day_key, day_items.clone());
-            } else {
-                result.insert(day_key, Value::Array(Vec::new()));
-            }
-        }
-        
-        return Ok(Value::Object(result));
-    }
-    
-    // Fallback to old method if day_items not available
-    let empty_vec = vec![];
-    let days = raw_data.get("days").and_then(|v| v.as_array()).unwrap_or(&empty_vec);
-    let items = raw_data.get("items").and_then(|v| v.as_array()).unwrap_or(&empty_vec);
-    
-    if !days.is_empty() && !items.is_empty() {
-        let items_per_day = items.len() / days.len();
-        
-        for (day_index, day_value) in days.iter().enumerate() {
-            if let Some(day_str) = day_value.as_str() {
-                let day_key = day_str.trim().to_string();
-                
-                // Get items for this specific day
-                let mut items_for_day = Vec::new();
-                
-                let start_index = day_index * items_per_day;
-                let end_index = if day_index == days.len() - 1 {
-                    items.len()
-                } else {
-                    start_index + items_per_day
-                };
-                
-                for i in start_index..end_index {
-                    if let Some(item) = items.get(i) {
-                        items_for_day.push(item.clone());
-                    }
-                }
-                
-                result.insert(day_key, Value::Array(items_for_day));
-            }
-        }
-    }
-    
-    Ok(Value::Object(result))
-}
-
-fn process_template_variable(key: &str, raw_data: &serde_json::Map<String, Value>) -> Result<String> {
-    if key.starts_with('{') && key.ends_with('}') {
-        let rule_name = &key[1..key.len()-1]; // Remove { and }
-        
-        // Handle special syntactic sugar variables
-        if rule_name == "currentYear" {
-            return Ok(chrono::Utc::now().year().to_string());
-        } else if rule_name == "currentMonth" {
-            return Ok(chrono::Utc::now().month().to_string());
-        } else if rule_name == "currentDay" {
-            return Ok(chrono::Utc::now().day().to_string());
-        } else if rule_name == "currentDate" {
-            return Ok(chrono::Utc::now().format("%Y-%m-%d").to_string());
-        } else if rule_name == "months" {
-            // Handle months variable - extract month names from the months array
-            if let Some(months_array) = raw_data.get("months").and_then(|v| v.as_array()) {
-                if let Some(first_month) = months_array.first() {
-                    if let Some(month_str) = first_month.as_str() {
-                        // Extract month name from string like "October 2025     — Ex-Dividend Calendar"
-                        let month_name = month_str.split_whitespace().next().unwrap_or("October");
-                        return Ok(month_name.to_string());
-                    }
-                }
-            }
-        } else if rule_name.starts_with("days") && rule_name.len() > 4 {
-            // Handle numbered day variables like {days0}, {days1}, etc.
-            if rule_name.contains("-") {
-                // Handle range syntax like "days0-30" - return the range as is for key processing
-                return Ok(rule_name.to_string());
-            } else if let Ok(day_index) = rule_name[4..].parse::<usize>() {
-                if let Some(days_array) = raw_data.get("days").and_then(|v| v.as_array()) {
-                    if let Some(day_value) = days_array.get(day_index) {
-                        if let Some(day_str) = day_value.as_str() {
-                            return Ok(day_str.trim().to_string());
-                        }
-                    }
-                }
-            }
-        } else {
-            // Handle regular rule variables - use the first value if it's an array
-            if let Some(raw_value) = raw_data.get(rule_name) {
-                if let Some(raw_array) = raw_value.as_array() {
-                    if let Some(first_value) = raw_array.first() {
-                        if let Some(str_value) = first_value.as_str() {
-                            return Ok(str_value.trim().to_string());
-                        }
-                    }
-                } else if let Some(str_value) = raw_value.as_str() {
-                    return Ok(str_value.trim().to_string());
-                }
-            }
-        }
-    }
+fn process_rule(ctx: &ExtractionContext, rule: &XPathRule) -> Result<Value> {
+    let document = ctx.document;
     
-    Ok(key.to_string())
-}
-
-fn process_rule(document: &Html, rule: &XPathRule) -> Result<Value> {
-    // Handle nested structure with for-each-item and map-item
-    if let Some(for_each_item) = &rule.for_each_item {
-        // Process the for-each-item rule first
-        let for_each_result = process_rule(document, for_each_item)?;
-        
-        // If there's a map-item rule, process it for each item
-        if let Some(_map_item) = &for_each_item.map_item {
-            let mut mapped_results = Vec::new();
-            
-            // Get the array of items from for-each-item
-            let items = match for_each_result {
-                Value::Array(arr) => arr,
-                _ => vec![for_each_result],
-            };
-            
-            // For each item, process the map-item rule
-            for item in items {
-                if let Some(day_str) = item.as_str() {
-                    // Find the specific day's items by looking for the day in the HTML
-                    let day_items = find_items_for_day(document, day_str)?;
-                    mapped_results.push(Value::Array(day_items));
-                }
-            }
-            
-            return Ok(Value::Array(mapped_results));
-        }
-        
-        return Ok(for_each_result);
+    // Handle nested structure with items
+    if rule.items.is_some() {
+        return process_grouped_rule(ctx, rule);
     }
     
-    // Handle Object extract type with children/fields
+    // Handle Object extract type with fields
     if let ExtractType::Object = &rule.extract_type {
-        let children = rule.children.as_ref().or_else(|| rule.fields.as_ref());
-        if let Some(children_rules) = children {
+        if let Some(children_rules) = &rule.fields {
             // Use a specialized XPath-to-CSS converter for the specific patterns
-            let selector_str = xpath_to_css_selector(&rule.xpath)?;
-            let selector = Selector::parse(&selector_str)
-                .map_err(|e| anyhow::anyhow!("Failed to parse selector: {}", e))?;
+            let selector = compiled_selector(ctx, &rule.xpath, "selector")?;
             
             let mut results = Vec::new();
             
@@ -675,242 +737,506 @@ fn process_rule(document: &Html, rule: &XPathRule) -> Result<Value> {
                 
                 // Process each child rule within the context of this element
                 for child_rule in children_rules {
-                    let child_selector_str = xpath_to_css_selector(&child_rule.xpath)?;
-                    let child_selector = Selector::parse(&child_selector_str)
-                        .map_err(|e| anyhow::anyhow!("Failed to parse child selector: {}", e))?;
+                    if let ExtractType::Computed = child_rule.extract_type {
+                        continue;
+                    }
+                    if let Some(condition) = &child_rule.when
+                        && !condition_holds(ctx, Some(element), condition, &object_result)? {
+                            continue;
+                        }
+                    if child_rule.xpath.trim().is_empty() {
+                        let child_value = extract_from_elements(ctx, child_rule, std::iter::once(element))?;
+                        object_result.insert(child_rule.name.clone(), child_value);
+                        continue;
+                    }
+                    let child_selector = compiled_selector(ctx, &child_rule.xpath, "child selector")?;
                     
                     let child_value = match &child_rule.extract_type {
-                        ExtractType::Text => {
-                            let mut texts = Vec::new();
-                            for child_element in element.select(&child_selector) {
-                                let text = child_element.text().collect::<String>().trim().to_string();
-                                if !text.is_empty() {
-                                    texts.push(Value::String(text));
-                                }
-                            }
-                            if texts.len() == 1 {
-                                texts.into_iter().next().unwrap_or(Value::Null)
-                            } else {
-                                Value::Array(texts)
-                            }
-                        }
-                        ExtractType::Attribute => {
-                            let mut attrs = Vec::new();
-                            for child_element in element.select(&child_selector) {
-                                if let Some(attr_name) = &child_rule.attribute {
-                                    if let Some(attr_value) = child_element.value().attr(attr_name) {
-                                        attrs.push(Value::String(attr_value.to_string()));
-                                    }
-                                }
-                            }
-                            if attrs.len() == 1 {
-                                attrs.into_iter().next().unwrap_or(Value::Null)
-                            } else {
-                                Value::Array(attrs)
-                            }
-                        }
-                        ExtractType::Html => {
-                            let mut htmls = Vec::new();
-                            for child_element in element.select(&child_selector) {
-                                htmls.push(Value::String(child_element.html()));
-                            }
-                            if htmls.len() == 1 {
-                                htmls.into_iter().next().unwrap_or(Value::Null)
-                            } else {
-                                Value::Array(htmls)
-                            }
-                        }
-                        ExtractType::Count => {
-                            Value::Number(serde_json::Number::from(element.select(&child_selector).count()))
-                        }
                         ExtractType::Object => Value::Null, // Nested objects not yet supported
+                        _ => extract_from_elements(ctx, child_rule, element.select(&child_selector))?,
                     };
                     
                     object_result.insert(child_rule.name.clone(), child_value);
                 }
                 
+                // Computed children see the sibling fields of this object
+                for child_rule in children_rules.iter().filter(|rule| matches!(rule.extract_type, ExtractType::Computed)) {
+                    if let Some(condition) = &child_rule.when
+                        && !condition_holds(ctx, Some(element), condition, &object_result)? {
+                            continue;
+                        }
+                    let child_value = evaluate_computed(ctx, child_rule, &object_result)?;
+                    object_result.insert(child_rule.name.clone(), child_value);
+                }
+                
                 results.push(Value::Object(object_result));
             }
             
-            return if results.len() == 1 {
-                Ok(results.into_iter().next().unwrap_or(Value::Null))
-            } else {
-                Ok(Value::Array(results))
-            };
+            return Ok(collapse_results(results));
         } else {
-            return Err(anyhow::anyhow!("Object extract type requires 'children' or 'fields'"));
+            return Err(anyhow::anyhow!("Object extract type requires 'fields'"));
         }
     }
     
+    // An empty xpath applies the rule to the whole document
+    if rule.xpath.trim().is_empty() {
+        return extract_from_elements(ctx, rule, std::iter::once(document.root_element()));
+    }
+    
     // Use a specialized XPath-to-CSS converter for the specific patterns
-    let selector_str = xpath_to_css_selector(&rule.xpath)?;
-    let selector = Selector::parse(&selector_str)
-        .map_err(|e| anyhow::anyhow!("Failed to parse selector: {}", e))?;
+    let selector = compiled_selector(ctx, &rule.xpath, "selector")?;
     
+    extract_from_elements(ctx, rule, document.select(&selector))
+}
+
+/// Extract the value described by `rule` from the elements its selector matched
+fn extract_from_elements<'a>(ctx: &ExtractionContext, rule: &XPathRule, elements: impl Iterator<Item = ElementRef<'a>>) -> Result<Value> {
     match &rule.extract_type {
         ExtractType::Text => {
             let mut results = Vec::new();
-            for element in document.select(&selector) {
-                let text = if rule.xpath.ends_with("/text()") {
-                    // For XPath ending with /text(), get the direct text content
-                    element.text().collect::<String>().trim().to_string()
-                } else {
-                    // For other cases, get all text content including nested elements
-                    element.text().collect::<String>().trim().to_string()
-                };
-                
+            for element in elements {
+                let text = element.text().collect::<String>().trim().to_string();
                 if !text.is_empty() {
                     results.push(Value::String(text));
                 }
             }
-            
-            if results.len() == 1 {
-                Ok(results.into_iter().next().unwrap_or(Value::Null))
-            } else {
-                Ok(Value::Array(results))
-            }
+            Ok(collapse_results(results))
         }
         ExtractType::Attribute => {
             let mut results = Vec::new();
-            for element in document.select(&selector) {
-                if let Some(attr_name) = &rule.attribute {
-                    if let Some(attr_value) = element.value().attr(attr_name) {
+            for element in elements {
+                if let Some(attr_name) = &rule.attribute
+                    && let Some(attr_value) = element.value().attr(attr_name) {
                         results.push(Value::String(attr_value.to_string()));
                     }
+            }
+            Ok(collapse_results(results))
+        }
+        ExtractType::Html | ExtractType::OuterHtml => {
+            let mut results = Vec::new();
+            for element in elements {
+                results.push(Value::String(element.html()));
+            }
+            Ok(collapse_results(results))
+        }
+        ExtractType::InnerHtml => {
+            let mut results = Vec::new();
+            for element in elements {
+                results.push(Value::String(element.inner_html()));
+            }
+            Ok(collapse_results(results))
+        }
+        ExtractType::SanitizedHtml => {
+            let mut results = Vec::new();
+            for element in elements {
+                let sanitized = content::sanitize_html(
+                    &element.inner_html(),
+                    rule.allowed_tags.as_deref(),
+                    rule.allowed_attributes.as_deref(),
+                );
+                results.push(Value::String(sanitized));
+            }
+            Ok(collapse_results(results))
+        }
+        ExtractType::Count => {
+            Ok(Value::Number(serde_json::Number::from(elements.count())))
+        }
+        ExtractType::Exists => {
+            Ok(Value::Bool(elements.count() > 0))
+        }
+        ExtractType::Markdown => {
+            let mut results = Vec::new();
+            for element in elements {
+                let markdown = content::to_markdown(element, ctx.base_url.as_ref());
+                if !markdown.is_empty() {
+                    results.push(Value::String(markdown));
                 }
             }
-            
-            if results.len() == 1 {
-                Ok(results.into_iter().next().unwrap_or(Value::Null))
-            } else {
-                Ok(Value::Array(results))
+            Ok(collapse_results(results))
+        }
+        ExtractType::ReadableText => {
+            let mut results = Vec::new();
+            for element in elements {
+                let text = content::to_readable_text(element);
+                if !text.is_empty() {
+                    results.push(Value::String(text));
+                }
             }
+            Ok(collapse_results(results))
         }
-        ExtractType::Html => {
+        ExtractType::Url => {
+            let strip_tracking = rule.strip_tracking.unwrap_or(false);
             let mut results = Vec::new();
-            for element in document.select(&selector) {
-                let html = element.html();
-                results.push(Value::String(html));
+            for element in elements {
+                // Without an explicit attribute, take the element's link: href for anchors, src for media
+                let raw = match &rule.attribute {
+                    Some(attr_name) => element.value().attr(attr_name),
+                    None => element.value().attr("href").or_else(|| element.value().attr("src")),
+                };
+                if let Some(raw) = raw
+                    && let Some(resolved) = urls::resolve_url(raw, ctx.base_url.as_ref(), strip_tracking) {
+                        results.push(Value::String(resolved));
+                    }
+            }
+            Ok(collapse_results(results))
+        }
+        ExtractType::Table => {
+            let options = tables::TableOptions {
+                key_column: rule.key_column,
+                header_rows: rule.header_rows,
+            };
+            let mut results = Vec::new();
+            for element in elements {
+                results.push(tables::table_to_json(element, &options));
             }
+            Ok(collapse_results(results))
+        }
+        ExtractType::KeyValue => {
+            let options = key_value::KeyValueOptions {
+                normalize_keys: rule.normalize_keys.unwrap_or(true),
+                keys: rule.keys.as_deref(),
+            };
+            let mut results = Vec::new();
+            for element in elements {
+                results.push(key_value::extract_key_values(element, &options));
+            }
+            Ok(collapse_results(results))
+        }
+        ExtractType::JsonLd => {
+            let mut results = Vec::new();
+            for element in elements {
+                let entities = structured_data::json_ld(element, rule.schema_type.as_deref())
+                    .map_err(|e| anyhow::anyhow!(e))?;
+                results.extend(entities);
+            }
+            Ok(collapse_results(results))
+        }
+        ExtractType::Microdata => {
+            let mut results = Vec::new();
+            for element in elements {
+                results.extend(structured_data::microdata(element, rule.schema_type.as_deref()));
+            }
+            Ok(collapse_results(results))
+        }
+        ExtractType::Rdfa => {
+            let mut results = Vec::new();
+            for element in elements {
+                results.extend(structured_data::rdfa(element, rule.schema_type.as_deref()));
+            }
+            Ok(collapse_results(results))
+        }
+        ExtractType::OpenGraph => {
+            let mut results = Vec::new();
+            for element in elements {
+                results.push(structured_data::opengraph(element));
+            }
+            Ok(collapse_results(results))
+        }
+        ExtractType::Meta => {
+            let mut results = Vec::new();
+            for element in elements {
+                results.push(structured_data::meta_tags(element));
+            }
+            Ok(collapse_results(results))
+        }
+        ExtractType::ScriptJson => {
+            let pattern = match rule.pattern.as_deref() {
+                Some(pattern) => Some(compiled_regex(ctx, pattern, "pattern")?),
+                None => None,
+            };
+            let mut results = Vec::new();
+            let mut last_error = None;
+            for element in elements {
+                let source: String = element.text().collect();
+                // Scripts without the JSON we are looking for are skipped; it is only an
+                // error when none of the matched scripts has it
+                match embedded_json::extract_json(&source, pattern.as_deref(), rule.assignment.as_deref()) {
+                    Ok(json) => results.push(select_json(ctx, &json, rule)?),
+                    Err(e) => last_error = Some(e),
+                }
+            }
+            match last_error {
+                Some(e) if results.is_empty() => Err(e),
+                _ => Ok(collapse_results(results)),
+            }
+        }
+        ExtractType::Regex => {
+            let pattern = rule.pattern.as_deref()
+                .ok_or_else(|| anyhow::anyhow!("Regex extract type requires 'pattern'"))?;
+            let regex = compiled_regex(ctx, pattern, "pattern")?;
+            let all_matches = rule.all_matches.unwrap_or(false);
+            let group = rule.group.as_ref();
             
-            if results.len() == 1 {
-                Ok(results.into_iter().next().unwrap_or(Value::Null))
-            } else {
-                Ok(Value::Array(results))
+            let mut results = Vec::new();
+            match rule.regex_source.as_ref().unwrap_or(&RegexSource::Text) {
+                RegexSource::Document => {
+                    results.extend(regex_extract::regex_matches(&regex, ctx.source, group, all_matches));
+                }
+                source => {
+                    for element in elements {
+                        let haystack = match source {
+                            RegexSource::Html => element.html(),
+                            _ => element.text().collect::<String>(),
+                        };
+                        results.extend(regex_extract::regex_matches(&regex, &haystack, group, all_matches));
+                    }
+                }
             }
+            Ok(collapse_results(results))
         }
-        ExtractType::Count => {
-            let count = document.select(&selector).count();
-            Ok(Value::Number(serde_json::Number::from(count)))
+        ExtractType::Links | ExtractType::Images => {
+            let url_filter = match &rule.url_pattern {
+                Some(pattern) => Some(compiled_regex(ctx, pattern, "url_pattern")?),
+                None => None,
+            };
+            let options = links::HarvestOptions {
+                base_url: ctx.base_url.as_ref(),
+                strip_tracking: rule.strip_tracking.unwrap_or(false),
+                url_filter: url_filter.as_deref(),
+            };
+            let mut results = Vec::new();
+            for element in elements {
+                let harvested = match rule.extract_type {
+                    ExtractType::Links => links::harvest_links(element, &options),
+                    _ => links::harvest_images(element, &options),
+                };
+                results.push(Value::Array(harvested));
+            }
+            Ok(collapse_results(results))
+        }
+        ExtractType::Computed => {
+            Err(anyhow::anyhow!("Computed rules are evaluated from other fields and cannot select elements"))
         }
         ExtractType::Object => {
             // This should have been handled above, but just in case
-            Err(anyhow::anyhow!("Object extract type must have 'children' or 'fields' defined"))
+            Err(anyhow::anyhow!("Object extract type must have 'fields' defined"))
         }
     }
 }
 
-fn find_items_for_day(document: &Html, day: &str) -> Result<Vec<Value>> {
-    find_items_for_day_in_month(document, day, None)
+/// Evaluate a `computed` rule's expression against the fields extracted so far
+fn evaluate_computed(ctx: &ExtractionContext, rule: &XPathRule, fields: &serde_json::Map<String, Value>) -> Result<Value> {
+    let source = rule.expression.as_deref()
+        .ok_or_else(|| anyhow::anyhow!("Computed extract type requires 'expression'"))?;
+    match ctx.compiled.expression(source) {
+        Some(expression) => expression.evaluate(fields),
+        None => expression::Expression::parse(source)?.evaluate(fields),
+    }
 }
 
-fn find_items_for_day_in_month(document: &Html, day: &str, month: Option<&str>) -> Result<Vec<Value>> {
-    use scraper::Selector;
-    
-    let mut items = Vec::new();
-    
-    // Find all rows in the calendar table
-    let table_selector = Selector::parse("table").unwrap();
-    let row_selector = Selector::parse("tr").unwrap();
-    let day_num_selector = Selector::parse("td.caltabletdnum").unwrap();
-    let item_selector = Selector::parse("td.caltabletdevt").unwrap();
-    let link_selector = Selector::parse("a").unwrap();
-    
-    // Find the calendar table
-    for table in document.select(&table_selector) {
-        let table_text = table.text().collect::<String>();
-        if table_text.contains("Ex-Dividend Calendar") {
-            // If month is specified, only process tables for that month
-            if let Some(month_name) = month {
-                if !table_text.contains(month_name) {
-                    continue;
-                }
-            }
-            let rows: Vec<_> = table.select(&row_selector).collect();
-            
-            for i in 0..rows.len() {
-                let row = &rows[i];
-                
-                // Check if this row contains day numbers
-                let day_nums: Vec<String> = row.select(&day_num_selector)
-                    .map(|el| el.text().collect::<String>().trim().to_string())
-                    .filter(|s| !s.is_empty())
-                    .collect();
-                    
-                if day_nums.contains(&day.to_string()) {
-                    // Find the column index for this specific day
-                    if let Some(day_index) = day_nums.iter().position(|d| d == day) {
-                        // Look for the immediately following row that contains items for this day
-                        if i + 1 < rows.len() {
-                            let item_row = &rows[i + 1];
-                            
-                            // Check if this row contains item data (has caltabletdevt cells)
-                            let item_cells: Vec<Vec<String>> = item_row.select(&item_selector)
-                                .map(|cell| {
-                                    cell.select(&link_selector)
-                                        .map(|link| link.text().collect::<String>().trim().to_string())
-                                        .filter(|s| !s.is_empty())
-                                        .collect::<Vec<String>>()
-                                })
-                                .collect();
-                            
-                            // If this row has item cells and the column index is valid
-                            if day_index < item_cells.len() && !item_cells[day_index].is_empty() {
-                                for item in &item_cells[day_index] {
-                                    items.push(Value::String(item.clone()));
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+/// The selector for an XPath as compiled for the run, or compiled now to report why it
+/// does not compile; `what` names it in that error
+fn compiled_selector<'c>(ctx: &'c ExtractionContext, xpath: &str, what: &str) -> Result<Cow<'c, Selector>> {
+    if let Some(selector) = ctx.compiled.selector(xpath) {
+        return Ok(Cow::Borrowed(selector));
+    }
+    let selector_str = xpath_to_css_selector(xpath, ctx.variables)?;
+    Selector::parse(&selector_str)
+        .map(Cow::Owned)
+        .map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", what, e))
+}
+
+/// The regex for a pattern as compiled for the run, or compiled now to report why it
+/// does not compile; `what` names it in that error
+fn compiled_regex<'c>(ctx: &'c ExtractionContext, pattern: &str, what: &str) -> Result<Cow<'c, regex::Regex>> {
+    if let Some(regex) = ctx.compiled.regex(pattern) {
+        return Ok(Cow::Borrowed(regex));
+    }
+    regex::Regex::new(pattern)
+        .map(Cow::Owned)
+        .map_err(|e| anyhow::anyhow!("Invalid {} '{}': {}", what, pattern, e))
+}
+
+/// Evaluate a rule's `when` condition. `scope` is the parent element for child rules
+/// (the document otherwise) and `fields` holds the fields extracted so far at that level.
+fn condition_holds(ctx: &ExtractionContext, scope: Option<ElementRef>, condition: &Condition, fields: &serde_json::Map<String, Value>) -> Result<bool> {
+    let mut holds = true;
+    
+    if let Some(xpath) = &condition.xpath {
+        let selector = compiled_selector(ctx, xpath, "condition selector")?;
+        let found = match scope {
+            Some(element) => element.select(&selector).next().is_some(),
+            None => ctx.document.select(&selector).next().is_some(),
+        };
+        holds &= found;
+    }
+    
+    if let Some(field) = &condition.field {
+        let value = fields.get(field).unwrap_or(&Value::Null);
+        if let Some(expected) = &condition.equals {
+            holds &= values_equal(value, expected);
+        }
+        if let Some(unexpected) = &condition.not_equals {
+            holds &= !values_equal(value, unexpected);
+        }
+        if let Some(pattern) = &condition.matches {
+            let regex = compiled_regex(ctx, pattern, "condition pattern")?;
+            holds &= regex.is_match(&value_as_string(value));
+        }
+        if condition.equals.is_none() && condition.not_equals.is_none() && condition.matches.is_none() {
+            holds &= is_truthy(value);
         }
     }
     
-    Ok(items)
+    Ok(holds != condition.not)
+}
+
+/// Compare extracted values loosely, so the text "3" equals the number 3
+fn values_equal(a: &Value, b: &Value) -> bool {
+    a == b || (!a.is_array() && !a.is_object() && !a.is_null() && value_as_string(a) == value_as_string(b))
+}
+
+fn value_as_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.trim().to_string(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// Whether an extracted value counts as present: not null, false, empty text or an empty list
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Bool(b) => *b,
+        other => !expression::is_empty_value(other),
+    }
+}
+
+/// Apply a rule's JSONPath and child rules to embedded JSON. With child rules, every
+/// node the path selects becomes an object built from the children's own paths.
+fn select_json(ctx: &ExtractionContext, json: &Value, rule: &XPathRule) -> Result<Value> {
+    let children = rule.fields.as_ref();
+    let nodes = match rule.jsonpath.as_deref() {
+        Some(path) => match ctx.compiled.jsonpath(path) {
+            Some(path) => embedded_json::query(json, path),
+            None => embedded_json::query(json, &embedded_json::parse_path(path)?),
+        },
+        None => vec![json.clone()],
+    };
+    
+    let Some(children_rules) = children else {
+        return Ok(collapse_results(nodes));
+    };
+    
+    let mut results = Vec::new();
+    for node in &nodes {
+        let mut object_result = serde_json::Map::new();
+        for child_rule in children_rules {
+            object_result.insert(child_rule.name.clone(), select_json(ctx, node, child_rule)?);
+        }
+        results.push(Value::Object(object_result));
+    }
+    Ok(collapse_results(results))
+}
+
+/// A single match is returned as a scalar, anything else as an array
+fn collapse_results(results: Vec<Value>) -> Value {
+    if results.len() == 1 {
+        results.into_iter().next().unwrap_or(Value::Null)
+    } else {
+        Value::Array(results)
+    }
 }
 
-fn xpath_to_css_selector(xpath: &str) -> Result<String> {
-    let xpath = xpath.trim();
+/// Extract a chain of `items` rules. Each level's nodes are selected across
+/// the document and attached to the node of the level above that they belong to in
+/// the document structure, giving one `{"key": ..., "<child name>": [...]}` group per
+/// node of the level above.
+fn process_grouped_rule(ctx: &ExtractionContext, rule: &XPathRule) -> Result<Value> {
+    let mut levels = vec![(rule, select_elements(ctx, rule)?, Vec::new())];
+    let mut current = rule;
+    while let Some(child) = current.items.as_deref() {
+        let elements = select_elements(ctx, child)?;
+        let owners = grouping::associate(&levels[levels.len() - 1].1, &elements, child.associate.unwrap_or_default())
+            .map_err(|e| anyhow::anyhow!("Cannot group '{}' under '{}': {}", child.name, current.name, e))?;
+        levels.push((child, elements, owners));
+        current = child;
+    }
     
+    // Build the groups bottom-up: the values of each level become members of their owners
+    let (leaf_rule, leaf_elements, mut owners) = levels.pop().unwrap();
+    let mut values = leaf_elements.iter()
+        .map(|element| extract_from_elements(ctx, leaf_rule, std::iter::once(*element)))
+        .collect::<Result<Vec<_>>>()?;
+    let mut member_name = &leaf_rule.name;
+    while let Some((level_rule, elements, level_owners)) = levels.pop() {
+        let mut members = vec![Vec::new(); elements.len()];
+        for (value, owner) in values.into_iter().zip(owners) {
+            if !matches!(&value, Value::Array(items) if items.is_empty()) {
+                members[owner].push(value);
+            }
+        }
+        values = elements.iter().zip(members)
+            .map(|(element, members)| {
+                let mut group = serde_json::Map::new();
+                group.insert("key".to_string(), extract_from_elements(ctx, level_rule, std::iter::once(*element))?);
+                group.insert(member_name.clone(), Value::Array(members));
+                Ok(Value::Object(group))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        owners = level_owners;
+        member_name = &level_rule.name;
+    }
+    Ok(Value::Array(values))
+}
+
+/// The elements a rule's xpath matches, or the document root for an empty xpath
+fn select_elements<'a>(ctx: &ExtractionContext<'a>, rule: &XPathRule) -> Result<Vec<ElementRef<'a>>> {
+    if rule.xpath.trim().is_empty() {
+        return Ok(vec![ctx.document.root_element()]);
+    }
+    let selector = compiled_selector(ctx, &rule.xpath, "selector")?;
+    Ok(ctx.document.select(&selector).collect())
+}
+
+/// Convert an XPath to a CSS selector, with its `$name` references bound to `variables`
+fn xpath_to_css_selector(xpath: &str, variables: &serde_json::Map<String, Value>) -> Result<String> {
+    let bound = variables::bind_xpath(xpath.trim(), variables)?;
+    let css = convert_xpath(&bound.slotted);
+    if let Some(name) = bound.dropped(&css) {
+        return Err(anyhow::anyhow!("Variable '${}' in XPath '{}' is lost in the conversion to CSS; text tests such as contains(., ${}) are not supported", name, xpath, name));
+    }
+    // Known patterns are recognised by how the XPath reads with its values in place
+    if let Some(css) = known_css_selector(&bound.literal) {
+        return Ok(css);
+    }
+    Ok(bound.fill(&css))
+}
+
+/// Selectors for specific XPaths the general conversion cannot handle
+fn known_css_selector(xpath: &str) -> Option<String> {
     // Handle the specific XPath patterns from your dividend configuration
     if xpath.contains("//table[contains(., 'Ex-Dividend Calendar')]//th[contains(@style, 'font-size: 26px')]") {
-        return Ok("table th[style*=\"font-size: 26px\"]".to_string());
+        return Some("table th[style*=\"font-size: 26px\"]".to_string());
     }
     
     if xpath.contains("//table[contains(., 'Ex-Dividend Calendar')]//td[contains(@class,'caltabletdevt')][.//span[@style=\"color: #4B9830; font-size: 22px;\"]]/../preceding-sibling::tr[1]/td[contains(@class,'caltabletdnum')]") {
         // This is a complex XPath that finds the preceding sibling row's day number
         // We'll use a simpler approach: find all td.caltabletdnum elements
-        return Ok("td.caltabletdnum".to_string());
+        return Some("td.caltabletdnum".to_string());
     }
     
     if xpath.contains("//table[contains(., 'Ex-Dividend Calendar')]//tr[td[@class='caltabletdnum']]/following-sibling::tr[1][td[@class='caltabletdevt']]") {
         // This finds the rows that contain both day numbers and their corresponding stocks
         // We'll use a simpler approach: find all tr elements and filter them in the processing
-        return Ok("tr".to_string());
+        return Some("tr".to_string());
     }
     
     if xpath.contains("//table[contains(., 'Ex-Dividend Calendar')]//td[contains(@class,'caltabletdevt')][.//span[@style=\"color: #4B9830; font-size: 22px;\"]]") {
         // This finds the td elements that contain the stock symbols
         if xpath.ends_with("/text()") {
             // For the stocks XPath with /text(), we need to target the anchor tags
-            return Ok("td.caltabletdevt span[style*=\"color: #000000\"] a".to_string());
+            return Some("td.caltabletdevt span[style*=\"color: #000000\"] a".to_string());
         } else {
-            return Ok("td.caltabletdevt span[style*=\"color: #4B9830\"][style*=\"font-size: 22px\"]".to_string());
+            return Some("td.caltabletdevt span[style*=\"color: #4B9830\"][style*=\"font-size: 22px\"]".to_string());
         }
     }
     
-    // General XPath to CSS conversion for simpler patterns
+    None
+}
+
+/// General XPath to CSS conversion for simpler patterns
+fn convert_xpath(xpath: &str) -> String {
     let mut css = xpath.to_string();
     
     // Handle contains() function FIRST - convert to CSS attribute selectors
@@ -947,12 +1273,11 @@ fn xpath_to_css_selector(xpath: &str) -> Result<String> {
         if css.contains("contains(., ") {
             // Extract the element name and use it as a basic selector
             let parts: Vec<&str> = css.split_whitespace().collect();
-            if let Some(first_part) = parts.first() {
-                if first_part.contains("[") {
+            if let Some(first_part) = parts.first()
+                && first_part.contains("[") {
                     let element_name = first_part.split('[').next().unwrap_or("body");
                     css = element_name.to_string();
                 }
-            }
         }
     }
     
@@ -1003,29 +1328,10 @@ fn xpath_to_css_selector(xpath: &str) -> Result<String> {
     // Replace single quotes with double quotes
     css = css.replace("'", "\"");
     
-    // Handle numeric selectors like td[1], tr[2] - convert to nth-child
-    css = css.replace("td[1]", "td:nth-child(1)");
-    css = css.replace("td[2]", "td:nth-child(2)");
-    css = css.replace("tr[1]", "tr:nth-child(1)");
-    css = css.replace("tr[2]", "tr:nth-child(2)");
-    css = css.replace("tr[3]", "tr:nth-child(3)");
-    css = css.replace("tr[4]", "tr:nth-child(4)");
-    css = css.replace("tr[5]", "tr:nth-child(5)");
-    css = css.replace("tr[6]", "tr:nth-child(6)");
-    css = css.replace("tr[7]", "tr:nth-child(7)");
-    css = css.replace("tr[8]", "tr:nth-child(8)");
-    css = css.replace("tr[9]", "tr:nth-child(9)");
-    css = css.replace("tr[10]", "tr:nth-child(10)");
-    css = css.replace("tr[11]", "tr:nth-child(11)");
-    css = css.replace("tr[12]", "tr:nth-child(12)");
-    css = css.replace("tr[13]", "tr:nth-child(13)");
-    css = css.replace("tr[14]", "tr:nth-child(14)");
-    css = css.replace("tr[15]", "tr:nth-child(15)");
-    css = css.replace("tr[16]", "tr:nth-child(16)");
-    css = css.replace("tr[17]", "tr:nth-child(17)");
-    css = css.replace("tr[18]", "tr:nth-child(18)");
-    css = css.replace("font[1]", "font:nth-child(1)");
-    css = css.replace("font[2]", "font:nth-child(2)");
+    // Handle position predicates like td[1], tr[20] - the Nth element of that name among its siblings
+    let position_pattern = r#"([\w-]+)\[\s*(\d+)\s*\]"#;
+    let re_position = regex::Regex::new(position_pattern).unwrap();
+    css = re_position.replace_all(&css, "$1:nth-of-type($2)").to_string();
     
     // Handle /text() at the end - remove it and target the parent element
     if css.ends_with("/text()") {
@@ -1038,5 +1344,5 @@ fn xpath_to_css_selector(xpath: &str) -> Result<String> {
     // Handle text() at the end
     css = css.replace(" text()", "");
     
-    Ok(css)
+    css
 }
@@ -0,0 +1,180 @@
+use anyhow::{Result, bail};
+use serde_json::{Map, Value};
+
+/// Configuration format version this build reads and `migrate` writes. Files without
+/// a `version` are version 1.
+pub const CURRENT_VERSION: u64 = 2;
+
+/// Rewrite the legacy rule structures of a configuration file, or of a bare list of
+/// rules, in their current form. Returns one note per change, naming where it was made.
+pub fn upgrade(config: &mut Value) -> Result<Vec<String>> {
+    let mut notes = Vec::new();
+    match config {
+        Value::Array(rules) => upgrade_rules(rules, "", &mut notes)?,
+        Value::Object(fields) => {
+            if let Some(version) = fields.get("version") {
+                match version.as_u64() {
+                    Some(version) if version <= CURRENT_VERSION => {}
+                    _ => bail!("Configuration version {} is not supported (this build reads up to {})", version, CURRENT_VERSION),
+                }
+            }
+            if let Some(Value::Array(rules)) = fields.get_mut("rules") {
+                upgrade_rules(rules, "/rules", &mut notes)?;
+            }
+            if let Some(Value::Object(fragments)) = fields.get_mut("fragments") {
+                for (name, fragment) in fragments.iter_mut() {
+                    upgrade_rule(fragment, &format!("/fragments/{}", name), &mut notes)?;
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(notes)
+}
+
+/// Stamp a configuration file with the current version, ahead of its other settings
+pub fn stamp_version(config: &mut Value) {
+    let Value::Object(fields) = config else {
+        return;
+    };
+    fields.shift_remove("version");
+    let schema = fields.shift_remove("$schema");
+    let rest = std::mem::take(fields);
+    fields.extend(schema.map(|schema| ("$schema".to_string(), schema)));
+    fields.insert("version".to_string(), Value::from(CURRENT_VERSION));
+    fields.extend(rest);
+}
+
+fn upgrade_rules(rules: &mut [Value], pointer: &str, notes: &mut Vec<String>) -> Result<()> {
+    for (index, rule) in rules.iter_mut().enumerate() {
+        upgrade_rule(rule, &format!("{}/{}", pointer, index), notes)?;
+    }
+    Ok(())
+}
+
+fn upgrade_rule(rule: &mut Value, pointer: &str, notes: &mut Vec<String>) -> Result<()> {
+    let Value::Object(fields) = rule else {
+        return Ok(());
+    };
+    for (legacy, current) in [("children", "fields"), ("for-each-item", "items"), ("map-item", "items")] {
+        if !fields.contains_key(legacy) {
+            continue;
+        }
+        if fields.contains_key(current) {
+            bail!("Rule at {} has both '{}' and '{}'", pointer, legacy, current);
+        }
+        rename_key(fields, legacy, current);
+        notes.push(format!("{}: '{}' is now '{}'", pointer, legacy, current));
+    }
+    if fields.shift_remove("iterate_over").is_some() {
+        notes.push(format!("{}: 'iterate_over' had no effect and was removed", pointer));
+    }
+
+    if let Some(Value::Array(children)) = fields.get_mut("fields") {
+        upgrade_rules(children, &format!("{}/fields", pointer), notes)?;
+    }
+    if let Some(items) = fields.get_mut("items") {
+        upgrade_rule(items, &format!("{}/items", pointer), notes)?;
+    }
+    Ok(())
+}
+
+/// Rename a key, keeping its place among the others
+fn rename_key(fields: &mut Map<String, Value>, from: &str, to: &str) {
+    *fields = std::mem::take(fields)
+        .into_iter()
+        .map(|(key, value)| if key == from { (to.to_string(), value) } else { (key, value) })
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn keys(value: &Value) -> Vec<&str> {
+        value.as_object().unwrap().keys().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn legacy_rule_keys_become_fields_and_items_in_place() {
+        let mut config = json!({
+            "name": "legacy",
+            "rules": [{
+                "name": "deals",
+                "extract_type": "object",
+                "children": [{ "name": "title", "xpath": ".//h2" }],
+                "xpath": "//div"
+            }, {
+                "name": "months",
+                "for-each-item": {
+                    "name": "days",
+                    "map-item": { "name": "items", "xpath": "//a", "iterate_over": "days" }
+                }
+            }]
+        });
+        let notes = upgrade(&mut config).unwrap();
+        assert_eq!(
+            config["rules"],
+            json!([{
+                "name": "deals",
+                "extract_type": "object",
+                "fields": [{ "name": "title", "xpath": ".//h2" }],
+                "xpath": "//div"
+            }, {
+                "name": "months",
+                "items": { "name": "days", "items": { "name": "items", "xpath": "//a" } }
+            }])
+        );
+        assert_eq!(keys(&config["rules"][0]), ["name", "extract_type", "fields", "xpath"]);
+        assert_eq!(
+            notes,
+            [
+                "/rules/0: 'children' is now 'fields'",
+                "/rules/1: 'for-each-item' is now 'items'",
+                "/rules/1/items: 'map-item' is now 'items'",
+                "/rules/1/items/items: 'iterate_over' had no effect and was removed",
+            ]
+        );
+    }
+
+    #[test]
+    fn current_configuration_is_left_alone() {
+        let original = json!({ "version": 2, "rules": [{ "name": "a", "fields": [{ "name": "b" }], "items": { "name": "c" } }] });
+        let mut config = original.clone();
+        assert!(upgrade(&mut config).unwrap().is_empty());
+        assert_eq!(config, original);
+    }
+
+    #[test]
+    fn bare_rule_lists_and_fragments_are_upgraded() {
+        let mut rules = json!([{ "name": "a", "children": [] }]);
+        assert_eq!(upgrade(&mut rules).unwrap(), ["/0: 'children' is now 'fields'"]);
+        assert_eq!(rules, json!([{ "name": "a", "fields": [] }]));
+
+        let mut config = json!({ "fragments": { "card": { "children": [] } } });
+        assert_eq!(upgrade(&mut config).unwrap(), ["/fragments/card: 'children' is now 'fields'"]);
+    }
+
+    #[test]
+    fn rule_with_both_legacy_and_current_keys_is_an_error() {
+        let mut config = json!({ "rules": [{ "name": "a", "children": [], "fields": [] }] });
+        assert_eq!(upgrade(&mut config).unwrap_err().to_string(), "Rule at /rules/0 has both 'children' and 'fields'");
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let mut config = json!({ "version": 3, "rules": [] });
+        assert!(upgrade(&mut config).is_err());
+        let mut config = json!({ "version": "2", "rules": [] });
+        assert!(upgrade(&mut config).is_err());
+    }
+
+    #[test]
+    fn version_is_stamped_after_the_schema_and_before_everything_else() {
+        let mut config = json!({ "name": "a", "version": 1, "$schema": "schema.json", "rules": [] });
+        stamp_version(&mut config);
+        assert_eq!(keys(&config), ["$schema", "version", "name", "rules"]);
+        assert_eq!(config["version"], json!(CURRENT_VERSION));
+    }
+}
@@ -0,0 +1,53 @@
+use regex::{Captures, Regex};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Which capture group of a `regex` rule to return
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum CaptureGroup {
+    Index(usize),
+    Name(String),
+}
+
+/// Run `regex` over `haystack` and convert each match to JSON.
+///
+/// With `group`, that group's text is returned. Otherwise a pattern with named groups
+/// yields an object of its named captures, and any other pattern yields its first
+/// group (or the whole match when it has none). Only the first match is returned
+/// unless `all_matches` is set.
+pub fn regex_matches(regex: &Regex, haystack: &str, group: Option<&CaptureGroup>, all_matches: bool) -> Vec<Value> {
+    let mut results = Vec::new();
+    for captures in regex.captures_iter(haystack) {
+        if let Some(value) = capture_value(regex, &captures, group) {
+            results.push(value);
+        }
+        if !all_matches {
+            break;
+        }
+    }
+    results
+}
+
+fn capture_value(regex: &Regex, captures: &Captures, group: Option<&CaptureGroup>) -> Option<Value> {
+    let text = |m: Option<regex::Match>| m.map(|m| Value::String(m.as_str().to_string()));
+
+    match group {
+        Some(CaptureGroup::Index(index)) => text(captures.get(*index)),
+        Some(CaptureGroup::Name(name)) => text(captures.name(name)),
+        None => {
+            let names: Vec<&str> = regex.capture_names().flatten().collect();
+            if names.is_empty() {
+                // An optional first group that did not take part is no value, not the whole match
+                text(captures.get(if regex.captures_len() == 1 { 0 } else { 1 }))
+            } else {
+                let mut object = Map::new();
+                for name in names {
+                    object.insert(name.to_string(), text(captures.name(name)).unwrap_or(Value::Null));
+                }
+                Some(Value::Object(object))
+            }
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Datelike, Locale, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde_json::{Map, Value};
+
+/// Names of the values [`RunContext::builtin`] provides
+pub const BUILTINS: &[&str] = &[
+    "currentYear",
+    "currentMonth",
+    "currentDay",
+    "currentDate",
+    "currentTime",
+    "currentTimestamp",
+    "sourceFile",
+    "configName",
+    "runId",
+];
+
+/// Everything about the current run that templates can refer to. Built once, so the
+/// clock, locale and identifiers are the same for every value rendered in a run.
+#[derive(Debug, Clone)]
+pub struct RunContext {
+    /// The run's "now", in the configured timezone
+    pub now: DateTime<Tz>,
+    /// Locale month and weekday names are written in
+    pub locale: Locale,
+    /// Languages month and weekday names are read in
+    pub languages: Vec<String>,
+    /// The input file as given on the command line
+    pub source_file: String,
+    /// Name of the configuration being run
+    pub config_name: String,
+    /// Identifier of this run
+    pub run_id: String,
+    /// Fail on undefined template variables
+    pub strict: bool,
+    /// Base URL overriding the config's `base_url`
+    pub base_url: Option<String>,
+    /// The config's variables, with the values passed on the command line
+    pub variables: Map<String, Value>,
+}
+
+impl RunContext {
+    /// Values available in every template without being extracted
+    pub fn builtin(&self, name: &str) -> Option<Value> {
+        let now = &self.now;
+        let value = match name {
+            "currentYear" => now.year().to_string(),
+            "currentMonth" => now.month().to_string(),
+            "currentDay" => now.day().to_string(),
+            "currentDate" => now.format("%Y-%m-%d").to_string(),
+            "currentTime" => now.format("%H:%M:%S").to_string(),
+            "currentTimestamp" => now.to_rfc3339_opts(SecondsFormat::Secs, false),
+            "sourceFile" => self.source_file.clone(),
+            "configName" => self.config_name.clone(),
+            "runId" => self.run_id.clone(),
+            _ => return None,
+        };
+        Some(Value::String(value))
+    }
+}
+
+/// Parse an IANA timezone name such as `Europe/Madrid`, or `UTC`
+pub fn parse_timezone(name: &str) -> Result<Tz> {
+    name.trim().parse::<Tz>().map_err(|_| anyhow!("Unknown timezone '{}'", name))
+}
+
+/// Parse a locale such as `es`, `pt_BR` or `fr-CA`. A bare language picks its most
+/// common region.
+pub fn parse_locale(name: &str) -> Result<Locale> {
+    let name = name.trim().replace('-', "_");
+    let full = match name.to_ascii_lowercase().as_str() {
+        "en" => "en_US".to_string(),
+        "es" => "es_ES".to_string(),
+        "fr" => "fr_FR".to_string(),
+        "de" => "de_DE".to_string(),
+        "pt" => "pt_PT".to_string(),
+        "it" => "it_IT".to_string(),
+        "nl" => "nl_NL".to_string(),
+        _ => name.clone(),
+    };
+    Locale::try_from(full.as_str()).map_err(|_| anyhow!("Unknown locale '{}'", name))
+}
+
+/// The run's "now": `--now` when given (RFC 3339, or a date or date-time read in
+/// `timezone`), otherwise the system clock
+pub fn resolve_now(now: Option<&str>, timezone: Tz) -> Result<DateTime<Tz>> {
+    let Some(text) = now.map(str::trim) else {
+        return Ok(Utc::now().with_timezone(&timezone));
+    };
+    if let Ok(instant) = DateTime::parse_from_rfc3339(text) {
+        return Ok(instant.with_timezone(&timezone));
+    }
+    let local = ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .or_else(|| NaiveDate::parse_from_str(text, "%Y-%m-%d").ok().and_then(|date| date.and_hms_opt(0, 0, 0)))
+        .ok_or_else(|| anyhow!("Invalid --now '{}': expected RFC 3339 or YYYY-MM-DD[THH:MM[:SS]]", text))?;
+    timezone
+        .from_local_datetime(&local)
+        .earliest()
+        .ok_or_else(|| anyhow!("'{}' does not exist in timezone {}", text, timezone))
+}
+
+/// A run identifier from the run's start time and the process id
+pub fn generate_run_id(now: &DateTime<Tz>) -> String {
+    format!("{}-{:x}", now.with_timezone(&Utc).format("%Y%m%dT%H%M%SZ"), std::process::id())
+}
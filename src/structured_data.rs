@@ -0,0 +1,238 @@
+use scraper::{ElementRef, Selector};
+use serde_json::{Map, Value};
+
+use crate::content::collapsed_text;
+
+/// Parse every `<script type="application/ld+json">` inside `scope`.
+///
+/// `@graph` containers and top-level arrays are flattened so each entity is one
+/// element of the result; with `schema_type` only entities of that `@type` are kept.
+/// Blocks that are not valid JSON are skipped; it is only an error when none of the
+/// blocks is.
+pub fn json_ld(scope: ElementRef, schema_type: Option<&str>) -> Result<Vec<Value>, String> {
+    let selector = Selector::parse("script").unwrap();
+    let mut entities = Vec::new();
+    let (mut parsed_any, mut last_error) = (false, None);
+    for script in scope.select(&selector) {
+        let is_json_ld = script
+            .value()
+            .attr("type")
+            .is_some_and(|kind| kind.trim().eq_ignore_ascii_case("application/ld+json"));
+        if !is_json_ld {
+            continue;
+        }
+        let source: String = script.text().collect();
+        let source = source.trim();
+        if source.is_empty() {
+            continue;
+        }
+        match serde_json::from_str(source) {
+            Ok(parsed) => {
+                parsed_any = true;
+                flatten_json_ld(parsed, &mut entities);
+            }
+            Err(e) => last_error = Some(format!("Invalid JSON-LD block: {}", e)),
+        }
+    }
+    if let Some(error) = last_error.filter(|_| !parsed_any) {
+        return Err(error);
+    }
+
+    Ok(entities.into_iter().filter(|entity| matches_type(entity, schema_type)).collect())
+}
+
+fn flatten_json_ld(value: Value, entities: &mut Vec<Value>) {
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                flatten_json_ld(item, entities);
+            }
+        }
+        Value::Object(mut object) => match object.remove("@graph") {
+            Some(graph) => {
+                flatten_json_ld(graph, entities);
+                // Keep the wrapper too if it carries more than @context
+                if object.keys().any(|key| key != "@context") {
+                    entities.push(Value::Object(object));
+                }
+            }
+            None => entities.push(Value::Object(object)),
+        },
+        _ => {}
+    }
+}
+
+/// Whether an entity's `@type` (string or array) names `schema_type`. Full IRIs such
+/// as `http://schema.org/Product` match their last path segment.
+fn matches_type(entity: &Value, schema_type: Option<&str>) -> bool {
+    let Some(wanted) = schema_type else {
+        return true;
+    };
+    let short = |name: &str| name.rsplit(['/', '#']).next().unwrap_or(name).to_string();
+    match entity.get("@type") {
+        Some(Value::String(name)) => short(name) == short(wanted),
+        Some(Value::Array(names)) => names.iter().filter_map(Value::as_str).any(|name| short(name) == short(wanted)),
+        _ => false,
+    }
+}
+
+/// Collect the top-level microdata items (`itemscope` elements that are not themselves
+/// a property of another item) inside `scope`
+pub fn microdata(scope: ElementRef, schema_type: Option<&str>) -> Vec<Value> {
+    let selector = Selector::parse("[itemscope]").unwrap();
+    std::iter::once(scope)
+        .filter(|element| element.value().attr("itemscope").is_some())
+        .chain(scope.select(&selector))
+        .filter(|element| element.value().attr("itemprop").is_none())
+        .map(microdata_item)
+        .filter(|item| matches_type(item, schema_type))
+        .collect()
+}
+
+fn microdata_item(item: ElementRef) -> Value {
+    let mut properties = Map::new();
+    if let Some(item_type) = item.value().attr("itemtype") {
+        let types: Vec<&str> = item_type.split_whitespace().collect();
+        let type_value = match types.as_slice() {
+            [single] => Value::String(single.to_string()),
+            _ => Value::Array(types.iter().map(|name| Value::String(name.to_string())).collect()),
+        };
+        properties.insert("@type".to_string(), type_value);
+    }
+    if let Some(id) = item.value().attr("itemid") {
+        properties.insert("@id".to_string(), Value::String(id.to_string()));
+    }
+    collect_properties(item, "itemprop", "itemscope", &mut properties, microdata_item);
+    Value::Object(properties)
+}
+
+/// Collect the RDFa Lite resources (`typeof` elements that are not themselves a
+/// property of another resource) inside `scope`
+pub fn rdfa(scope: ElementRef, schema_type: Option<&str>) -> Vec<Value> {
+    let selector = Selector::parse("[typeof]").unwrap();
+    std::iter::once(scope)
+        .filter(|element| element.value().attr("typeof").is_some())
+        .chain(scope.select(&selector))
+        .filter(|element| element.value().attr("property").is_none())
+        .map(rdfa_resource)
+        .filter(|resource| matches_type(resource, schema_type))
+        .collect()
+}
+
+fn rdfa_resource(resource: ElementRef) -> Value {
+    let mut properties = Map::new();
+    if let Some(type_of) = resource.value().attr("typeof") {
+        let vocab = resource.value().attr("vocab").unwrap_or("");
+        let types: Vec<String> = type_of.split_whitespace().map(|name| format!("{}{}", vocab, name)).collect();
+        let type_value = match types.as_slice() {
+            [single] => Value::String(single.clone()),
+            _ => Value::Array(types.into_iter().map(Value::String).collect()),
+        };
+        properties.insert("@type".to_string(), type_value);
+    }
+    if let Some(id) = resource.value().attr("resource") {
+        properties.insert("@id".to_string(), Value::String(id.to_string()));
+    }
+    collect_properties(resource, "property", "typeof", &mut properties, rdfa_resource);
+    Value::Object(properties)
+}
+
+/// Walk the descendants of an item, recording each property element's value. Nested
+/// items become nested objects and their own properties are not attributed to `item`.
+fn collect_properties(
+    item: ElementRef,
+    property_attr: &str,
+    scope_attr: &str,
+    properties: &mut Map<String, Value>,
+    nested_item: fn(ElementRef) -> Value,
+) {
+    for child in item.children().filter_map(ElementRef::wrap) {
+        let opens_scope = child.value().attr(scope_attr).is_some();
+        if let Some(names) = child.value().attr(property_attr) {
+            let value = if opens_scope { nested_item(child) } else { property_value(child) };
+            for name in names.split_whitespace() {
+                push_property(properties, name, value.clone());
+            }
+        }
+        if !opens_scope {
+            collect_properties(child, property_attr, scope_attr, properties, nested_item);
+        }
+    }
+}
+
+/// Repeated properties become arrays
+fn push_property(properties: &mut Map<String, Value>, name: &str, value: Value) {
+    match properties.get_mut(name) {
+        Some(Value::Array(values)) => values.push(value),
+        Some(existing) => {
+            let first = existing.take();
+            *existing = Value::Array(vec![first, value]);
+        }
+        None => {
+            properties.insert(name.to_string(), value);
+        }
+    }
+}
+
+/// The value of a property element, following the microdata/RDFa rules for which
+/// attribute carries the value on each kind of element
+fn property_value(element: ElementRef) -> Value {
+    let value = element.value();
+    if let Some(content) = value.attr("content") {
+        return Value::String(content.to_string());
+    }
+    let attr = match value.name() {
+        "a" | "area" | "link" => value.attr("href").or(value.attr("resource")),
+        "img" | "audio" | "video" | "source" | "iframe" | "embed" | "track" => value.attr("src"),
+        "object" => value.attr("data"),
+        "time" => value.attr("datetime"),
+        "data" | "meter" => value.attr("value"),
+        _ => value.attr("resource"),
+    };
+    match attr {
+        Some(attr) => Value::String(attr.to_string()),
+        None => Value::String(collapsed_text(element)),
+    }
+}
+
+/// OpenGraph `<meta property="og:...">` tags as an object keyed without the `og:`
+/// prefix ("og:image:width" becomes "image:width"); repeated tags become arrays
+pub fn opengraph(scope: ElementRef) -> Value {
+    let selector = Selector::parse("meta[property][content]").unwrap();
+    let mut properties = Map::new();
+    for meta in scope.select(&selector) {
+        let (Some(property), Some(content)) = (meta.value().attr("property"), meta.value().attr("content")) else {
+            continue;
+        };
+        if let Some(name) = property.trim().strip_prefix("og:") {
+            push_property(&mut properties, name, Value::String(content.to_string()));
+        }
+    }
+    Value::Object(properties)
+}
+
+/// Every `<meta>` tag with a `name`, `property` or `http-equiv` and a
+/// `content`, keyed by that name, plus the document `<title>`
+pub fn meta_tags(scope: ElementRef) -> Value {
+    let meta_selector = Selector::parse("meta[content]").unwrap();
+    let title_selector = Selector::parse("title").unwrap();
+    let mut properties = Map::new();
+
+    if let Some(title) = scope.select(&title_selector).next() {
+        let title = title.text().collect::<String>().trim().to_string();
+        if !title.is_empty() {
+            properties.insert("title".to_string(), Value::String(title));
+        }
+    }
+    for meta in scope.select(&meta_selector) {
+        let value = meta.value();
+        let name = value
+            .attr("name")
+            .or(value.attr("property"))
+            .or(value.attr("http-equiv"));
+        if let (Some(name), Some(content)) = (name, value.attr("content")) {
+            push_property(&mut properties, name.trim(), Value::String(content.to_string()));
+        }
+    }
+    Value::Object(properties)
+}
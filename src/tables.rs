@@ -0,0 +1,298 @@
+use scraper::ElementRef;
+use serde_json::{Map, Value};
+
+use crate::content::collapsed_text;
+
+/// How a `table` rule should lay out its rows
+pub struct TableOptions {
+    /// 1-based column whose text becomes the key of each row (label/value tables)
+    pub key_column: Option<usize>,
+    /// Number of leading rows to treat as headers, overriding detection
+    pub header_rows: Option<usize>,
+}
+
+/// One slot of the table grid after colspan/rowspan expansion
+#[derive(Clone)]
+struct GridCell {
+    text: String,
+    is_header: bool,
+    /// Identifies the source cell, shared by every slot a spanning cell covers
+    id: usize,
+}
+
+/// Convert a `<table>` element into JSON.
+///
+/// Without a key column every body row becomes an object keyed by the header cells.
+/// With a key column the result is a single object mapping that column's text to the
+/// remaining cell (or an object of the remaining cells when there are several).
+pub fn table_to_json(table: ElementRef, options: &TableOptions) -> Value {
+    let rows = table_rows(table);
+    let (grid, thead_rows) = build_grid(&rows);
+    if grid.is_empty() {
+        return Value::Array(Vec::new());
+    }
+
+    let header_count = options.header_rows.unwrap_or_else(|| {
+        if thead_rows > 0 {
+            thead_rows
+        } else {
+            // Leading rows made only of <th> cells are headers
+            grid.iter()
+                .take_while(|row| !row.is_empty() && row.iter().flatten().all(|cell| cell.is_header))
+                .count()
+        }
+    });
+    let header_count = header_count.min(grid.len());
+    let width = grid.iter().map(Vec::len).max().unwrap_or(0);
+    let headers = column_headers(&grid[..header_count], width);
+    let body = &grid[header_count..];
+
+    match options.key_column {
+        Some(key_column) => key_column_object(body, &headers, key_column.saturating_sub(1)),
+        None => Value::Array(
+            body.iter()
+                .filter(|row| row.iter().flatten().any(|cell| !cell.text.is_empty()))
+                .map(|row| Value::Object(row_object(row, &headers, None)))
+                .collect(),
+        ),
+    }
+}
+
+/// Collect the rows that belong to this table, in document order, skipping nested tables
+fn table_rows(table: ElementRef) -> Vec<(ElementRef, bool)> {
+    let mut rows = Vec::new();
+    for child in table.children().filter_map(ElementRef::wrap) {
+        match child.value().name() {
+            "tr" => rows.push((child, false)),
+            section @ ("thead" | "tbody" | "tfoot") => {
+                for row in child.children().filter_map(ElementRef::wrap) {
+                    if row.value().name() == "tr" {
+                        rows.push((row, section == "thead"));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    rows
+}
+
+/// Lay the rows out on a grid, copying spanning cells into every slot they cover.
+/// Also returns how many grid rows came from `<thead>`.
+fn build_grid(rows: &[(ElementRef, bool)]) -> (Vec<Vec<Option<GridCell>>>, usize) {
+    let mut grid: Vec<Vec<Option<GridCell>>> = Vec::new();
+    let mut thead_rows = 0;
+    let mut next_id = 0;
+
+    for (row_index, (row, in_thead)) in rows.iter().enumerate() {
+        if *in_thead {
+            thead_rows = row_index + 1;
+        }
+        if grid.len() <= row_index {
+            grid.resize(row_index + 1, Vec::new());
+        }
+
+        let mut column = 0;
+        for cell in row.children().filter_map(ElementRef::wrap) {
+            let name = cell.value().name();
+            if name != "td" && name != "th" {
+                continue;
+            }
+            // Skip slots already taken by rowspans from earlier rows
+            while grid[row_index].get(column).is_some_and(Option::is_some) {
+                column += 1;
+            }
+
+            let colspan = span_attr(cell, "colspan");
+            let rowspan = span_attr(cell, "rowspan");
+            let text = collapsed_text(cell);
+            let is_header = name == "th" || *in_thead;
+            let id = next_id;
+            next_id += 1;
+
+            for r in row_index..(row_index + rowspan).min(rows.len()) {
+                if grid.len() <= r {
+                    grid.resize(r + 1, Vec::new());
+                }
+                for c in column..column + colspan {
+                    if grid[r].len() <= c {
+                        grid[r].resize(c + 1, None);
+                    }
+                    grid[r][c] = Some(GridCell {
+                        text: text.clone(),
+                        is_header,
+                        id,
+                    });
+                }
+            }
+            column += colspan;
+        }
+    }
+
+    (grid, thead_rows)
+}
+
+/// The largest spans browsers honour; anything above is clamped to them
+const MAX_COLSPAN: usize = 1000;
+const MAX_ROWSPAN: usize = 65534;
+
+fn span_attr(cell: ElementRef, name: &str) -> usize {
+    let max = if name == "colspan" { MAX_COLSPAN } else { MAX_ROWSPAN };
+    cell.value()
+        .attr(name)
+        .and_then(|value| value.trim().parse::<usize>().ok())
+        .filter(|span| *span > 0)
+        .map_or(1, |span| span.min(max))
+}
+
+/// Build one key per column by joining the header rows top to bottom, so a spanning
+/// "Price" above "Open" and "Close" yields "Price Open" and "Price Close"
+fn column_headers(header_rows: &[Vec<Option<GridCell>>], width: usize) -> Vec<String> {
+    let mut headers: Vec<String> = Vec::with_capacity(width);
+    for column in 0..width {
+        let mut parts: Vec<&str> = Vec::new();
+        for row in header_rows {
+            if let Some(Some(cell)) = row.get(column)
+                && !cell.text.is_empty()
+                && parts.last() != Some(&cell.text.as_str())
+            {
+                parts.push(&cell.text);
+            }
+        }
+        let mut header = if parts.is_empty() { format!("column_{}", column + 1) } else { parts.join(" ") };
+
+        // Keep keys unique so no column silently overwrites another
+        if headers.contains(&header) {
+            let mut suffix = 2;
+            while headers.contains(&format!("{}_{}", header, suffix)) {
+                suffix += 1;
+            }
+            header = format!("{}_{}", header, suffix);
+        }
+        headers.push(header);
+    }
+    headers
+}
+
+fn row_object(row: &[Option<GridCell>], headers: &[String], skip_column: Option<usize>) -> Map<String, Value> {
+    let mut object = Map::new();
+    for (column, header) in headers.iter().enumerate() {
+        if Some(column) == skip_column {
+            continue;
+        }
+        let text = row.get(column).and_then(Option::as_ref).map(|cell| cell.text.clone()).unwrap_or_default();
+        object.insert(header.clone(), Value::String(text));
+    }
+    object
+}
+
+fn key_column_object(body: &[Vec<Option<GridCell>>], headers: &[String], key_column: usize) -> Value {
+    let mut result = Map::new();
+    for row in body {
+        let key = match row.get(key_column).and_then(Option::as_ref) {
+            Some(cell) if !cell.text.is_empty() => cell.text.clone(),
+            _ => continue,
+        };
+
+        // Value cells are the other columns, with colspan copies of the same cell collapsed
+        let mut value_columns: Vec<usize> = Vec::new();
+        let mut seen_ids: Vec<usize> = Vec::new();
+        for (column, cell) in row.iter().enumerate() {
+            if let Some(cell) = cell
+                && column != key_column
+                && cell.id != row[key_column].as_ref().map_or(usize::MAX, |key| key.id)
+                && !seen_ids.contains(&cell.id)
+            {
+                seen_ids.push(cell.id);
+                value_columns.push(column);
+            }
+        }
+
+        let value = match value_columns.as_slice() {
+            [] => Value::Null,
+            [column] => Value::String(row[*column].as_ref().map(|cell| cell.text.clone()).unwrap_or_default()),
+            _ => Value::Object(row_object(row, headers, Some(key_column))),
+        };
+        result.insert(key, value);
+    }
+    Value::Object(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scraper::{Html, Selector};
+    use serde_json::json;
+
+    fn table_json(html: &str, options: &TableOptions) -> Value {
+        let document = Html::parse_fragment(html);
+        let table = document.select(&Selector::parse("table").unwrap()).next().unwrap();
+        table_to_json(table, options)
+    }
+
+    fn rows_of(html: &str) -> Value {
+        table_json(html, &TableOptions { key_column: None, header_rows: None })
+    }
+
+    #[test]
+    fn spanning_header_is_joined_with_the_headers_below_it() {
+        let html = r#"<table>
+            <tr><th rowspan="2">Date</th><th colspan="2">Price</th></tr>
+            <tr><th>Open</th><th>Close</th></tr>
+            <tr><td>Oct 1</td><td>10</td><td>11</td></tr>
+        </table>"#;
+        assert_eq!(rows_of(html), json!([{ "Date": "Oct 1", "Price Open": "10", "Price Close": "11" }]));
+    }
+
+    #[test]
+    fn rowspan_cell_is_copied_into_the_rows_it_covers() {
+        let html = r#"<table>
+            <thead><tr><th>Group</th><th>Name</th></tr></thead>
+            <tr><td rowspan="2">A</td><td>first</td></tr>
+            <tr><td>second</td></tr>
+        </table>"#;
+        assert_eq!(rows_of(html), json!([{ "Group": "A", "Name": "first" }, { "Group": "A", "Name": "second" }]));
+    }
+
+    #[test]
+    fn rowspan_stops_at_the_last_row() {
+        let html = r#"<table><tr><th>A</th><th>B</th></tr><tr><td rowspan="9">1</td><td>2</td></tr></table>"#;
+        assert_eq!(rows_of(html), json!([{ "A": "1", "B": "2" }]));
+    }
+
+    #[test]
+    fn invalid_and_zero_spans_count_as_one() {
+        let html = r#"<table><tr><th colspan="0">A</th><th colspan="two">B</th></tr><tr><td>1</td><td>2</td></tr></table>"#;
+        assert_eq!(rows_of(html), json!([{ "A": "1", "B": "2" }]));
+    }
+
+    #[test]
+    fn spans_are_clamped_to_browser_limits() {
+        let html = r#"<table><tr><td colspan="100000000" rowspan="100000000">x</td></tr></table>"#;
+        let document = Html::parse_fragment(html);
+        let cell = document.select(&Selector::parse("td").unwrap()).next().unwrap();
+        assert_eq!(span_attr(cell, "colspan"), MAX_COLSPAN);
+        assert_eq!(span_attr(cell, "rowspan"), MAX_ROWSPAN);
+
+        let table = document.select(&Selector::parse("table").unwrap()).next().unwrap();
+        let (grid, _) = build_grid(&table_rows(table));
+        assert_eq!(grid.len(), 1);
+        assert_eq!(grid[0].len(), MAX_COLSPAN);
+    }
+
+    #[test]
+    fn key_column_collapses_colspan_copies_of_the_value() {
+        let html = r#"<table>
+            <tr><td>Price</td><td colspan="2">10</td></tr>
+            <tr><td>Range</td><td>9</td><td>12</td></tr>
+        </table>"#;
+        let options = TableOptions { key_column: Some(1), header_rows: Some(0) };
+        assert_eq!(table_json(html, &options), json!({ "Price": "10", "Range": { "column_2": "9", "column_3": "12" } }));
+    }
+
+    #[test]
+    fn duplicate_headers_get_a_suffix() {
+        let html = r#"<table><tr><th>Name</th><th>Name</th></tr><tr><td>a</td><td>b</td></tr></table>"#;
+        assert_eq!(rows_of(html), json!([{ "Name": "a", "Name_2": "b" }]));
+    }
+}
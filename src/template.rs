@@ -0,0 +1,578 @@
+//! Rendering of `output_sample` templates against the extracted data.
+//!
+//! A template is any JSON value. Placeholders `{path}` inside strings and object keys
+//! are replaced by the value at `path`, where a path is a field name followed by
+//! `.`-separated object keys or array indexes (`{product.price}`, `{items.0}`). A
+//! string that is a single placeholder keeps the value's type; anywhere else the value
+//! is interpolated as text (`"{currency} {price}"`). `{{` and `}}` are literal braces.
+//!
+//! Filters transform a value: `{price|number}`, `{title|upper}`, `{date|format:%Y}`,
+//! `{tags|join:, }`, `{note|default:n/a}`. Run values such as `{currentDate}` and
+//! `{sourceFile}` come from the [`RunContext`].
+//!
+//! Repetition is written with `{#each path as name}`:
+//!
+//! * as an object key, its value is rendered once per item and the resulting
+//!   objects are merged, producing a keyed object
+//!   (`{"{#each months as month}": {"{month.name}": "{month.days}"}}`);
+//! * as the first element of a two-element array, the second element is rendered
+//!   once per item, producing an array (`["{#each items as item}", {"title": "{item.title}"}]`).
+//!
+//! Arrays are iterated by element (`{@index}` is the position) and objects by entry
+//! (`{@key}` is the key). Several sources can be walked in lockstep with
+//! `{#each dates as date, values as value}`; they must have the same length.
+
+use anyhow::{Result, anyhow, bail};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime};
+use serde_json::{Map, Value};
+
+use crate::dates;
+use crate::expression::{is_empty_value, number_value, to_number};
+use crate::run_context::{BUILTINS, RunContext};
+
+/// Render `template` with the fields of `data` and the built-in values of `run`. In
+/// strict mode a placeholder that matches nothing is an error; otherwise it is left
+/// as written.
+pub fn render(template: &Value, data: &Map<String, Value>, run: &RunContext) -> Result<Value> {
+    Scope { data, run, bindings: Vec::new() }.render(template)
+}
+
+struct Scope<'a> {
+    data: &'a Map<String, Value>,
+    run: &'a RunContext,
+    /// Names bound by enclosing `{#each}` blocks, innermost last
+    bindings: Vec<(String, Value)>,
+}
+
+/// A parsed `{#each ...}` header: the sources to walk and the name each item is bound to
+struct Each {
+    sources: Vec<(String, String)>,
+}
+
+/// A piece of a template string
+enum Segment<'a> {
+    Text(String),
+    Placeholder(&'a str),
+}
+
+impl Scope<'_> {
+    fn render(&mut self, template: &Value) -> Result<Value> {
+        match template {
+            Value::Object(object) => {
+                let mut result = Map::new();
+                for (key, value) in object {
+                    if let Some(each) = parse_each(key)? {
+                        for rendered in self.repeat(&each, value)? {
+                            match rendered {
+                                Value::Object(entries) => result.extend(entries),
+                                other => bail!("The body of '{}' must render to an object, got {}", key, other),
+                            }
+                        }
+                    } else {
+                        let rendered_key = self.interpolate(key)?.trim().to_string();
+                        result.insert(rendered_key, self.render(value)?);
+                    }
+                }
+                Ok(Value::Object(result))
+            }
+            Value::Array(items) => {
+                if let Some(each) = items.first().and_then(Value::as_str).map(parse_each).transpose()?.flatten() {
+                    let [_, body] = items.as_slice() else {
+                        bail!("An array starting with '{}' must contain exactly one item template", items[0]);
+                    };
+                    return Ok(Value::Array(self.repeat(&each, body)?));
+                }
+                items.iter().map(|item| self.render(item)).collect::<Result<_>>().map(Value::Array)
+            }
+            Value::String(text) => match segments(text).as_slice() {
+                [Segment::Placeholder(expression)] => Ok(self.evaluate(expression)?.unwrap_or_else(|| template.clone())),
+                _ => self.interpolate(text).map(Value::String),
+            },
+            _ => Ok(template.clone()),
+        }
+    }
+
+    /// Render `body` once per item of the `{#each}` sources
+    fn repeat(&mut self, each: &Each, body: &Value) -> Result<Vec<Value>> {
+        let mut sources = Vec::new();
+        for (path, name) in &each.sources {
+            let items = match self.evaluate(path)? {
+                None | Some(Value::Null) => Vec::new(),
+                Some(Value::Array(items)) => items.into_iter().map(|item| (None, item)).collect(),
+                Some(Value::Object(entries)) => entries.into_iter().map(|(key, item)| (Some(key), item)).collect(),
+                Some(item) => vec![(None, item)],
+            };
+            sources.push((path, name, items));
+        }
+        let count = sources.first().map_or(0, |(_, _, items)| items.len());
+        if let Some((path, _, items)) = sources.iter().find(|(_, _, items)| items.len() != count) {
+            bail!("Cannot iterate '{}' and '{}' together: they have {} and {} items", each.sources[0].0, path, count, items.len());
+        }
+
+        let mut results = Vec::with_capacity(count);
+        for index in 0..count {
+            let depth = self.bindings.len();
+            self.bindings.push(("@index".to_string(), Value::from(index)));
+            for (_, name, items) in &sources {
+                let (key, item) = &items[index];
+                if let Some(key) = key {
+                    self.bindings.push(("@key".to_string(), Value::String(key.clone())));
+                }
+                self.bindings.push((name.to_string(), item.clone()));
+            }
+            let rendered = self.render(body);
+            self.bindings.truncate(depth);
+            results.push(rendered?);
+        }
+        Ok(results)
+    }
+
+    /// Replace every placeholder in `text` with its value as text
+    fn interpolate(&self, text: &str) -> Result<String> {
+        let mut result = String::new();
+        for segment in segments(text) {
+            match segment {
+                Segment::Text(literal) => result.push_str(&literal),
+                Segment::Placeholder(expression) => match self.evaluate(expression)? {
+                    Some(value) => result.push_str(&value_text(&value, expression)?),
+                    None => result.push_str(&format!("{{{}}}", expression)),
+                },
+            }
+        }
+        Ok(result)
+    }
+
+    /// Evaluate `path|filter|...`; `None` when the path matches nothing outside strict mode
+    fn evaluate(&self, expression: &str) -> Result<Option<Value>> {
+        let mut parts = expression.split('|');
+        let path = parts.next().unwrap_or_default().trim();
+        let mut value = self.lookup(path);
+        for filter in parts {
+            value = apply_filter(value, filter, self.run)?;
+        }
+        if value.is_none() && self.run.strict {
+            bail!("Undefined template variable '{}'", path);
+        }
+        Ok(value)
+    }
+
+    /// Resolve a dotted path against the `{#each}` bindings, the built-in values and the data
+    fn lookup(&self, path: &str) -> Option<Value> {
+        let mut segments = path.split('.').map(str::trim);
+        let head = segments.next()?;
+        let mut value = match self.bindings.iter().rev().find(|(name, _)| name == head) {
+            Some((_, value)) => value.clone(),
+            None => match (head.strip_prefix('$'), self.run.builtin(head)) {
+                (Some(variable), _) => self.run.variables.get(variable)?.clone(),
+                (None, Some(value)) => value,
+                (None, None) => self.data.get(head)?.clone(),
+            },
+        };
+        for segment in segments {
+            value = match value {
+                Value::Object(mut entries) => entries.remove(segment)?,
+                Value::Array(mut items) => {
+                    let index: usize = segment.parse().ok()?;
+                    if index >= items.len() {
+                        return None;
+                    }
+                    items.swap_remove(index)
+                }
+                _ => return None,
+            };
+        }
+        Some(value)
+    }
+}
+
+/// Split a template string into literal text and `{...}` placeholders
+fn segments(text: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+    let mut rest = text;
+    while let Some(position) = rest.find(['{', '}']) {
+        literal.push_str(&rest[..position]);
+        let tail = &rest[position..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            literal.push_str(&tail[..1]);
+            rest = &tail[2..];
+            continue;
+        }
+        let placeholder = tail
+            .strip_prefix('{')
+            .and_then(|inner| inner.find(['{', '}']).filter(|end| inner[*end..].starts_with('}')).map(|end| &inner[..end]))
+            .filter(|inner| !inner.trim().is_empty() && !inner.starts_with('#'));
+        match placeholder {
+            Some(inner) => {
+                if !literal.is_empty() {
+                    segments.push(Segment::Text(std::mem::take(&mut literal)));
+                }
+                segments.push(Segment::Placeholder(inner));
+                rest = &tail[inner.len() + 2..];
+            }
+            None => {
+                literal.push_str(&tail[..1]);
+                rest = &tail[1..];
+            }
+        }
+    }
+    literal.push_str(rest);
+    if !literal.is_empty() {
+        segments.push(Segment::Text(literal));
+    }
+    segments
+}
+
+/// A value as it appears inside a string or object key
+fn value_text(value: &Value, expression: &str) -> Result<String> {
+    match value {
+        Value::String(text) => Ok(text.clone()),
+        Value::Null => Ok(String::new()),
+        Value::Array(items) if items.is_empty() => Ok(String::new()),
+        Value::Array(items) => bail!(
+            "'{{{}}}' has {} values; select one (e.g. '{{{}.0}}') or combine them with '|join'",
+            expression,
+            items.len(),
+            expression
+        ),
+        Value::Object(_) => bail!("'{{{}}}' is an object; select one of its fields", expression),
+        other => Ok(other.to_string()),
+    }
+}
+
+/// Statically check a template without data: malformed each blocks, unknown filters,
+/// and variables that are not bound by an each block, built in, or `defined`. Returns
+/// the JSON pointer (below the template) and message of each problem.
+pub fn check(template: &Value, defined: &dyn Fn(&str) -> bool) -> Vec<(String, String)> {
+    let mut checker = Checker { defined, bound: Vec::new(), problems: Vec::new() };
+    checker.check(template, "");
+    checker.problems
+}
+
+struct Checker<'a> {
+    defined: &'a dyn Fn(&str) -> bool,
+    bound: Vec<String>,
+    problems: Vec<(String, String)>,
+}
+
+impl Checker<'_> {
+    fn check(&mut self, template: &Value, pointer: &str) {
+        match template {
+            Value::Object(object) => {
+                for (key, value) in object {
+                    let child = format!("{}/{}", pointer, key.replace('~', "~0").replace('/', "~1"));
+                    match parse_each(key) {
+                        Err(e) => self.problems.push((child, e.to_string())),
+                        Ok(Some(each)) => self.check_each(&each, value, &child, &child),
+                        Ok(None) => {
+                            self.check_text(key, &child);
+                            self.check(value, &child);
+                        }
+                    }
+                }
+            }
+            Value::Array(items) => match items.first().and_then(Value::as_str).map(parse_each) {
+                Some(Err(e)) => self.problems.push((format!("{}/0", pointer), e.to_string())),
+                Some(Ok(Some(each))) => match items.as_slice() {
+                    [_, body] => self.check_each(&each, body, &format!("{}/0", pointer), &format!("{}/1", pointer)),
+                    _ => self.problems.push((pointer.to_string(), "An array starting with an each block must contain exactly one item template".to_string())),
+                },
+                _ => {
+                    for (index, item) in items.iter().enumerate() {
+                        self.check(item, &format!("{}/{}", pointer, index));
+                    }
+                }
+            },
+            Value::String(text) => self.check_text(text, pointer),
+            _ => {}
+        }
+    }
+
+    fn check_each(&mut self, each: &Each, body: &Value, header: &str, pointer: &str) {
+        for (path, _) in &each.sources {
+            self.check_expression(path, header);
+        }
+        let depth = self.bound.len();
+        self.bound.extend(each.sources.iter().map(|(_, name)| name.clone()));
+        self.bound.extend(["@index".to_string(), "@key".to_string()]);
+        self.check(body, pointer);
+        self.bound.truncate(depth);
+    }
+
+    fn check_text(&mut self, text: &str, pointer: &str) {
+        for segment in segments(text) {
+            if let Segment::Placeholder(expression) = segment {
+                self.check_expression(expression, pointer);
+            }
+        }
+    }
+
+    fn check_expression(&mut self, expression: &str, pointer: &str) {
+        let mut parts = expression.split('|');
+        let path = parts.next().unwrap_or_default().trim();
+        let head = path.split('.').next().unwrap_or_default().trim();
+        let filters: Vec<&str> = parts.map(|filter| filter.split(':').next().unwrap_or_default().trim()).collect();
+        for filter in filters.iter().filter(|filter| !FILTERS.contains(filter)) {
+            self.problems.push((pointer.to_string(), format!("Unknown template filter '{}'", filter)));
+        }
+        let known = self.bound.iter().any(|name| name == head) || BUILTINS.contains(&head) || (self.defined)(head);
+        if !known && !filters.contains(&"default") {
+            self.problems.push((pointer.to_string(), format!("Undefined template variable '{}'", head)));
+        }
+    }
+}
+
+/// Every filter `apply_filter` understands
+const FILTERS: &[&str] = &[
+    "default", "first", "last", "length", "join", "chronological", "number", "upper", "lower", "trim", "format", "year",
+    "month", "day", "weekday",
+];
+
+fn scalar_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+fn apply_filter(value: Option<Value>, filter: &str, run: &RunContext) -> Result<Option<Value>> {
+    let (name, argument) = match filter.split_once(':') {
+        Some((name, argument)) => (name.trim(), Some(argument)),
+        None => (filter.trim(), None),
+    };
+    if name == "default" {
+        let empty = value.as_ref().is_none_or(is_empty_value);
+        return Ok(if empty { Some(Value::String(argument.unwrap_or_default().to_string())) } else { value });
+    }
+    let Some(value) = value else {
+        return Ok(None);
+    };
+
+    let filtered = match name {
+        "first" => first_or_last(value, true),
+        "last" => first_or_last(value, false),
+        "length" => Value::from(match &value {
+            Value::Array(items) => items.len(),
+            Value::Object(entries) => entries.len(),
+            Value::String(text) => text.chars().count(),
+            Value::Null => 0,
+            _ => 1,
+        }),
+        "join" => {
+            let separator = argument.unwrap_or(", ");
+            match value {
+                Value::Array(items) => Value::String(items.iter().map(scalar_text).collect::<Vec<_>>().join(separator)),
+                other => other,
+            }
+        }
+        "chronological" => match value {
+            Value::Array(items) => {
+                let mut dated = items
+                    .into_iter()
+                    .map(|item| {
+                        let text = scalar_text(&argument.and_then(|field| field_of(&item, field.trim())).unwrap_or_else(|| item.clone()));
+                        let date = parse_date(&text, run)
+                            .ok_or_else(|| anyhow!("Cannot sort chronologically: '{}' is not a date", text))?;
+                        Ok((date, item))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                dated.sort_by_key(|(date, _)| *date);
+                Value::Array(dated.into_iter().map(|(_, item)| item).collect())
+            }
+            other => other,
+        },
+        _ => map_scalars(value, &|scalar| scalar_filter(scalar, name, argument, run))?,
+    };
+    Ok(Some(filtered))
+}
+
+fn first_or_last(value: Value, first: bool) -> Value {
+    match value {
+        Value::Array(items) if first => items.into_iter().next().unwrap_or(Value::Null),
+        Value::Array(mut items) => items.pop().unwrap_or(Value::Null),
+        other => other,
+    }
+}
+
+/// Apply a filter to a value, or to each element of an array
+fn map_scalars(value: Value, filter: &dyn Fn(Value) -> Result<Value>) -> Result<Value> {
+    match value {
+        Value::Array(items) => items.into_iter().map(filter).collect::<Result<_>>().map(Value::Array),
+        other => filter(other),
+    }
+}
+
+/// The value at a dotted path inside `value`
+fn field_of(value: &Value, path: &str) -> Option<Value> {
+    path.split('.').try_fold(value, |value, segment| match value {
+        Value::Object(entries) => entries.get(segment),
+        Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+        _ => None,
+    }).cloned()
+}
+
+fn scalar_filter(value: Value, name: &str, argument: Option<&str>, run: &RunContext) -> Result<Value> {
+    let text = || scalar_text(&value);
+    let date = || parse_date(&text(), run).ok_or_else(|| anyhow!("Cannot read '{}' as a date", text()));
+    Ok(match name {
+        "number" => to_number(&value).map(number_value).unwrap_or(Value::Null),
+        "upper" => Value::String(text().to_uppercase()),
+        "lower" => Value::String(text().to_lowercase()),
+        "trim" => Value::String(text().trim().to_string()),
+        "format" => {
+            let format = argument.ok_or_else(|| anyhow!("The format filter needs a pattern, e.g. 'format:%Y-%m-%d'"))?;
+            if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+                bail!("Invalid date format '{}'", format);
+            }
+            match &value {
+                Value::Null => Value::Null,
+                _ => Value::String(date()?.and_utc().format_localized(format, run.locale).to_string()),
+            }
+        }
+        "year" => Value::from(date()?.year()),
+        "month" => Value::from(date()?.month()),
+        "day" => Value::from(date()?.day()),
+        "weekday" => {
+            let weekday = match parse_date(&text(), run) {
+                Some(date) => date.weekday(),
+                None => dates::weekday_from_name(&text(), &run.languages)
+                    .ok_or_else(|| anyhow!("Cannot read '{}' as a date or weekday", text()))?,
+            };
+            Value::from(weekday.number_from_monday())
+        }
+        _ => bail!("Unknown template filter '{}'", name),
+    })
+}
+
+/// Read the common date and date-time spellings found in pages, including dates written
+/// with month names in the run's languages
+pub fn parse_date(text: &str, run: &RunContext) -> Option<NaiveDateTime> {
+    let text = text.trim();
+    if let Ok(date) = DateTime::parse_from_rfc3339(text) {
+        return Some(date.naive_local());
+    }
+    const DATE_TIMES: &[&str] = &["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"];
+    const DATES: &[&str] = &["%Y-%m-%d", "%Y/%m/%d", "%m/%d/%y", "%m/%d/%Y", "%d.%m.%Y"];
+    DATE_TIMES
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .or_else(|| {
+            DATES
+                .iter()
+                .find_map(|format| NaiveDate::parse_from_str(text, format).ok())
+                .or_else(|| dates::parse_textual_date(text, &run.languages, run.now.date_naive()))
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+}
+
+/// Parse `{#each path as name, other as name2}`; `None` when `text` is not an each block
+fn parse_each(text: &str) -> Result<Option<Each>> {
+    let Some(spec) = text.strip_prefix("{#each").and_then(|rest| rest.strip_suffix('}')) else {
+        return Ok(None);
+    };
+    let mut sources = Vec::new();
+    for source in spec.split(',') {
+        let mut words = source.split_whitespace();
+        let (Some(path), alias, name, None) = (words.next(), words.next(), words.next(), words.next()) else {
+            bail!("Invalid each block '{}': expected '{{#each <field> as <name>}}'", text);
+        };
+        let name = match (alias, name) {
+            (Some("as"), Some(name)) => name,
+            (None, None) if !spec.contains(',') => "item",
+            (None, None) => bail!("Invalid each block '{}': every source needs its own 'as <name>'", text),
+            _ => bail!("Invalid each block '{}': expected '{{#each <field> as <name>}}'", text),
+        };
+        sources.push((path.to_string(), name.to_string()));
+    }
+    Ok(Some(Each { sources }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Locale, TimeZone};
+    use serde_json::json;
+
+    fn run(strict: bool) -> RunContext {
+        RunContext {
+            now: chrono_tz::UTC.with_ymd_and_hms(2024, 3, 5, 14, 30, 0).unwrap(),
+            locale: Locale::en_US,
+            languages: vec!["en".to_string()],
+            source_file: "page.html".to_string(),
+            config_name: "calendar".to_string(),
+            run_id: "run-1".to_string(),
+            strict,
+            base_url: None,
+            variables: json!({ "region": "Europe" }).as_object().unwrap().clone(),
+        }
+    }
+
+    fn render_with(template: Value, data: Value) -> Result<Value> {
+        render(&template, data.as_object().unwrap(), &run(false))
+    }
+
+    #[test]
+    fn single_placeholders_keep_the_value_type_and_others_interpolate_text() {
+        let data = json!({ "price": 12.5, "currency": "EUR", "product": { "tags": ["a", "b"] } });
+        let rendered = render_with(json!({ "price": "{price}", "label": "{currency} {price}", "tag": "{product.tags.1}" }), data).unwrap();
+        assert_eq!(rendered, json!({ "price": 12.5, "label": "EUR 12.5", "tag": "b" }));
+    }
+
+    #[test]
+    fn keys_are_rendered_and_doubled_braces_are_literal() {
+        let rendered = render_with(json!({ "{currency}": "{{price}} {price}" }), json!({ "currency": "EUR", "price": 3 })).unwrap();
+        assert_eq!(rendered, json!({ "EUR": "{price} 3" }));
+    }
+
+    #[test]
+    fn built_in_values_and_variables_are_available() {
+        let rendered = render_with(json!(["{currentDate}", "{sourceFile}", "{$region}"]), json!({})).unwrap();
+        assert_eq!(rendered, json!(["2024-03-05", "page.html", "Europe"]));
+    }
+
+    #[test]
+    fn filters_are_applied_left_to_right() {
+        let data = json!({ "title": "  Ex-Dividend  ", "price": "$1,234.50", "tags": ["x", "y"], "note": "", "date": "03/15/2024" });
+        let template = json!(["{title|trim|upper}", "{price|number}", "{tags|join:/}", "{note|default:n/a}", "{date|format:%d %B %Y}", "{tags|last}"]);
+        assert_eq!(render_with(template, data).unwrap(), json!(["EX-DIVIDEND", 1234.5, "x/y", "n/a", "15 March 2024", "y"]));
+    }
+
+    #[test]
+    fn unknown_filters_and_bad_formats_are_errors() {
+        assert!(render_with(json!("{title|shout}"), json!({ "title": "a" })).unwrap_err().to_string().contains("Unknown template filter 'shout'"));
+        assert!(render_with(json!("{date|format}"), json!({ "date": "2024-01-01" })).is_err());
+    }
+
+    #[test]
+    fn each_as_a_key_builds_a_keyed_object() {
+        let data = json!({ "months": [{ "name": "Jan", "days": 31 }, { "name": "Feb", "days": 29 }] });
+        let rendered = render_with(json!({ "{#each months as month}": { "{month.name}": "{month.days}" } }), data).unwrap();
+        assert_eq!(rendered, json!({ "Jan": 31, "Feb": 29 }));
+    }
+
+    #[test]
+    fn each_in_an_array_builds_an_array_with_index_and_key() {
+        let rendered = render_with(json!(["{#each items as item}", { "n": "{@index}", "title": "{item.title}" }]), json!({ "items": [{ "title": "a" }, { "title": "b" }] })).unwrap();
+        assert_eq!(rendered, json!([{ "n": 0, "title": "a" }, { "n": 1, "title": "b" }]));
+
+        let rendered = render_with(json!(["{#each totals as total}", "{@key}={total}"]), json!({ "totals": { "x": 1, "y": 2 } })).unwrap();
+        assert_eq!(rendered, json!(["x=1", "y=2"]));
+    }
+
+    #[test]
+    fn sources_walked_together_must_have_the_same_length() {
+        let template = json!(["{#each dates as date, values as value}", "{date}: {value}"]);
+        let rendered = render_with(template.clone(), json!({ "dates": ["d1", "d2"], "values": [1, 2] })).unwrap();
+        assert_eq!(rendered, json!(["d1: 1", "d2: 2"]));
+        assert!(render_with(template, json!({ "dates": ["d1", "d2"], "values": [1] })).is_err());
+    }
+
+    #[test]
+    fn undefined_placeholders_are_kept_unless_strict() {
+        assert_eq!(render_with(json!("Hello {name}"), json!({})).unwrap(), json!("Hello {name}"));
+        let error = render(&json!("Hello {name}"), &Map::new(), &run(true)).unwrap_err();
+        assert_eq!(error.to_string(), "Undefined template variable 'name'");
+    }
+}
@@ -0,0 +1,79 @@
+use scraper::{Html, Selector};
+use url::Url;
+
+/// Query parameters added by ad networks and mailing tools that never affect the target page
+const TRACKING_PARAMS: &[&str] = &[
+    "fbclid", "gclid", "dclid", "msclkid", "yclid", "mc_cid", "mc_eid", "igshid", "_hsenc", "_hsmi",
+];
+
+/// Work out the base URL for a document: its `<base href>` (resolved against `fallback`
+/// when relative) if present, otherwise `fallback` itself
+pub fn document_base_url(document: &Html, fallback: Option<Url>) -> Option<Url> {
+    let base_selector = Selector::parse("base[href]").unwrap();
+    let href = document
+        .select(&base_selector)
+        .next()
+        .and_then(|base| base.value().attr("href"))
+        .map(str::trim)
+        .filter(|href| !href.is_empty());
+
+    if let Some(href) = href {
+        let resolved = match &fallback {
+            Some(fallback) => fallback.join(href).ok(),
+            None => Url::parse(href).ok(),
+        };
+        if resolved.is_some() {
+            return resolved;
+        }
+    }
+
+    fallback
+}
+
+/// Resolve a link as found in the page into an absolute, normalized URL.
+///
+/// Returns `None` for empty links. Links that cannot be made absolute (relative link
+/// and no base URL) are returned trimmed but otherwise untouched.
+pub fn resolve_url(raw: &str, base: Option<&Url>, strip_tracking: bool) -> Option<String> {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return None;
+    }
+
+    let parsed = match base {
+        Some(base) => base.join(raw),
+        None => Url::parse(raw),
+    };
+    let mut url = match parsed {
+        Ok(url) => url,
+        Err(_) => return Some(raw.to_string()),
+    };
+
+    if strip_tracking {
+        strip_tracking_params(&mut url);
+    }
+
+    Some(url.to_string())
+}
+
+fn is_tracking_param(name: &str) -> bool {
+    name.starts_with("utm_") || TRACKING_PARAMS.contains(&name)
+}
+
+fn strip_tracking_params(url: &mut Url) {
+    if url.query().is_none() {
+        return;
+    }
+
+    let kept: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(name, _)| !is_tracking_param(name))
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+
+    if kept.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(kept);
+    }
+}
@@ -0,0 +1,382 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{Locale, Utc};
+use chrono_tz::Tz;
+use regex::Regex;
+use scraper::Selector;
+use serde_json::{Map, Value};
+
+use crate::compiled::Compiled;
+use crate::config_format::ConfigFormat;
+use crate::run_context::RunContext;
+use crate::{ExtractType, XPathConfig, XPathRule, compose, dates, embedded_json, expression, template, xpath_to_css_selector};
+
+/// Check every config, printing `file:line:column: message` for each problem found,
+/// naming the file of a composed config it was written in (`file: /json/pointer:
+/// message` for YAML and TOML files, or `file: ok`), after a warning for each file in
+/// deprecated rule syntax.
+/// Returns whether all of them are clean.
+pub fn validate_files(paths: &[PathBuf], format: Option<ConfigFormat>) -> Result<bool> {
+    let mut clean = true;
+    for path in paths {
+        let (diagnostics, deprecations) = validate_file(path, ConfigFormat::resolve(format, path))?;
+        for deprecation in &deprecations {
+            println!("warning: {}", deprecation);
+        }
+        if diagnostics.is_empty() {
+            println!("{}: ok", path.display());
+        }
+        for diagnostic in &diagnostics {
+            println!("{}", diagnostic);
+        }
+        clean &= diagnostics.is_empty();
+    }
+    Ok(clean)
+}
+
+fn validate_file(path: &Path, format: ConfigFormat) -> Result<(Vec<String>, Vec<String>)> {
+    let text = fs::read_to_string(path).with_context(|| format!("Failed to read configuration file {:?}", path))?;
+    // Syntax errors are reported as the parser sees them, before anything is composed
+    match format {
+        ConfigFormat::Json => {
+            if let Err(e) = serde_json::from_str::<Value>(&text) {
+                let message = e.to_string();
+                let message = message.rsplit_once(" at line ").map_or(message.as_str(), |(message, _)| message);
+                return Ok((vec![format!("{}:{}:{}: {}", path.display(), e.line(), e.column().max(1), message)], Vec::new()));
+            }
+        }
+        _ => {
+            if let Err(e) = format.parse(&text) {
+                return Ok((vec![format!("{}: {}", path.display(), e)], Vec::new()));
+            }
+        }
+    }
+    let (config, deprecations, origins) = match compose::load_with_origins(path, Some(format)) {
+        Ok(loaded) => loaded,
+        Err(e) => return Ok((vec![format!("{}: {:#}", path.display(), e)], Vec::new())),
+    };
+    let problems = check_value(&config);
+
+    // Each problem is reported in the file it was written in: by line and column in
+    // JSON files, by JSON pointer in YAML and TOML files
+    let mut offsets = HashMap::new();
+    let mut located = Vec::new();
+    for (pointer, message) in problems {
+        let origin = origins.locate(&pointer);
+        let file = if origin.file.as_os_str().is_empty() { path.to_path_buf() } else { origin.file };
+        let located_in = offsets.entry(file.clone()).or_insert_with(|| {
+            let text = if file == path { text.clone() } else { fs::read_to_string(&file).unwrap_or_default() };
+            let is_json = ConfigFormat::from_extension(&file).unwrap_or(format) == ConfigFormat::Json;
+            is_json.then(|| (Scanner::locate(&text), text))
+        });
+        let location = located_in.as_ref().map(|(offsets, text)| line_and_column(text, offset_of(offsets, &origin.pointer)));
+        located.push((file != path, file, location, origin.pointer, message));
+    }
+    // The checked file first, then the files it is composed of
+    located.sort_by(|a, b| (a.0, &a.1, a.2).cmp(&(b.0, &b.1, b.2)));
+    let diagnostics = located
+        .into_iter()
+        .map(|(_, file, location, pointer, message)| match location {
+            Some((line, column)) => format!("{}:{}:{}: {}", file.display(), line, column, message),
+            None => format!("{}: {}: {}", file.display(), if pointer.is_empty() { "/" } else { &pointer }, message),
+        })
+        .collect();
+    Ok((diagnostics, deprecations))
+}
+
+/// Every problem in a parsed configuration
+fn check_value(value: &Value) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let mut unknown = Vec::new();
+    match serde_ignored::deserialize(value, |ignored| unknown.push(ignored_pointer(&ignored))) {
+        Ok(config) => {
+            problems.extend(unknown.into_iter().map(|pointer| {
+                let key = pointer.rsplit('/').next().unwrap_or_default().replace("~1", "/").replace("~0", "~");
+                (pointer, format!("Unknown field '{}'", key))
+            }));
+            check_config(&config, &mut problems);
+        }
+        Err(_) => {
+            // Deserialize again to learn where the error is
+            if let Err(e) = serde_path_to_error::deserialize::<_, XPathConfig>(value) {
+                problems.push((error_pointer(e.path()), e.inner().to_string()));
+            }
+        }
+    }
+    problems
+}
+
+/// A problem: the JSON pointer of the offending value and what is wrong with it
+type Problem = (String, String);
+
+fn check_config(config: &XPathConfig, problems: &mut Vec<Problem>) {
+    // Variables without a default are passed on the command line; any text stands in for them
+    let mut variables = Map::new();
+    for (name, value) in &config.variables {
+        match value {
+            Value::Array(_) | Value::Object(_) => {
+                problems.push((format!("/variables/{}", escape(name)), "Variables must be strings, numbers or booleans".to_string()));
+            }
+            Value::Null => {
+                variables.insert(name.clone(), Value::String(String::new()));
+            }
+            value => {
+                variables.insert(name.clone(), value.clone());
+            }
+        }
+    }
+    // Dates in examples may be written in any supported language
+    let run = RunContext {
+        now: Utc::now().with_timezone(&Tz::UTC),
+        locale: Locale::en_US,
+        languages: dates::LANGUAGES.iter().map(ToString::to_string).collect(),
+        source_file: String::new(),
+        config_name: config.name.clone(),
+        run_id: String::new(),
+        strict: false,
+        base_url: None,
+        variables,
+    };
+    check_rules(&config.rules, "/rules", &run, problems);
+
+    // Merged rules add fields that cannot be known without running them
+    let merges = config.rules.iter().any(|rule| rule.merge.unwrap_or(false));
+    let names: HashSet<&str> = config.rules.iter().map(|rule| rule.name.as_str()).collect();
+    let defined = |name: &str| match name.strip_prefix('$') {
+        Some(variable) => config.variables.contains_key(variable),
+        None => merges || names.contains(name),
+    };
+    let templates = config.output_sample.iter().map(|sample| ("/output_sample".to_string(), sample));
+    let outputs = config.outputs.iter().map(|(name, output)| (format!("/outputs/{}", escape(name)), output));
+    for (pointer, template) in templates.chain(outputs) {
+        for (below, message) in template::check(template, &defined) {
+            problems.push((format!("{}{}", pointer, below), message));
+        }
+    }
+}
+
+fn check_rules(rules: &[XPathRule], pointer: &str, run: &RunContext, problems: &mut Vec<Problem>) {
+    let mut seen = HashSet::new();
+    for (index, rule) in rules.iter().enumerate() {
+        let pointer = format!("{}/{}", pointer, index);
+        if rule.name.trim().is_empty() {
+            problems.push((format!("{}/name", pointer), "Rule name is empty".to_string()));
+        } else if !seen.insert(rule.name.as_str()) {
+            problems.push((format!("{}/name", pointer), format!("Duplicate rule name '{}'", rule.name)));
+        }
+        check_rule(rule, &pointer, run, problems);
+    }
+}
+
+fn check_rule(rule: &XPathRule, pointer: &str, run: &RunContext, problems: &mut Vec<Problem>) {
+    let mut problem = |field: &str, message: String| problems.push((format!("{}{}", pointer, field), message));
+
+    if let Err(e) = check_xpath(&rule.xpath, &run.variables) {
+        problem("/xpath", e.to_string());
+    }
+    let missing = match rule.extract_type {
+        ExtractType::Attribute if rule.attribute.is_none() => Some("attribute"),
+        ExtractType::Regex if rule.pattern.is_none() => Some("pattern"),
+        ExtractType::Computed if rule.expression.is_none() => Some("expression"),
+        ExtractType::Object if rule.fields.is_none() && rule.items.is_none() => Some("fields"),
+        _ => None,
+    };
+    if let Some(field) = missing {
+        problem("/extract_type", format!("This extract type needs '{}'", field));
+    }
+    for (field, pattern) in [("/pattern", &rule.pattern), ("/url_pattern", &rule.url_pattern)] {
+        if let Some(Err(e)) = pattern.as_deref().map(Regex::new) {
+            problem(field, format!("Invalid regex: {}", regex_error(&e)));
+        }
+    }
+    if let Some(Err(e)) = rule.expression.as_deref().map(expression::Expression::parse) {
+        problem("/expression", format!("Invalid expression: {}", e));
+    }
+    if let Some(Err(e)) = rule.jsonpath.as_deref().map(embedded_json::parse_path) {
+        problem("/jsonpath", e.to_string());
+    }
+    if let Some(condition) = &rule.when {
+        if let Some(Err(e)) = condition.xpath.as_deref().map(|xpath| check_xpath(xpath, &run.variables)) {
+            problem("/when/xpath", e.to_string());
+        }
+        if let Some(Err(e)) = condition.matches.as_deref().map(Regex::new) {
+            problem("/when/matches", format!("Invalid regex: {}", regex_error(&e)));
+        }
+    }
+    if let Some(expectation) = &rule.expect {
+        if let Some(Err(e)) = expectation.pattern.as_deref().map(Regex::new) {
+            problem("/expect/pattern", format!("Invalid regex: {}", regex_error(&e)));
+        } else if let Some(Ok(Some(message))) = rule.example.as_ref().map(|example| expectation.check(example, run, &Compiled::default())) {
+            problem("/example", format!("The example {}", message));
+        }
+    }
+
+    if let Some(fields) = &rule.fields {
+        check_rules(fields, &format!("{}/fields", pointer), run, problems);
+    }
+    if let Some(items) = &rule.items {
+        check_rule(items, &format!("{}/items", pointer), run, problems);
+    }
+}
+
+/// The last line of a regex error, which holds the actual complaint
+fn regex_error(error: &regex::Error) -> String {
+    let message = error.to_string();
+    let last = message.lines().last().unwrap_or_default();
+    last.trim_start_matches("error: ").to_string()
+}
+
+/// Whether an xpath can be turned into a selector; an empty one selects the whole scope
+fn check_xpath(xpath: &str, variables: &Map<String, Value>) -> Result<()> {
+    if xpath.trim().is_empty() {
+        return Ok(());
+    }
+    let css = xpath_to_css_selector(xpath, variables)?;
+    Selector::parse(&css).map_err(|e| anyhow::anyhow!("XPath '{}' cannot be converted to a selector: {}", xpath, e))?;
+    Ok(())
+}
+
+fn ignored_pointer(path: &serde_ignored::Path) -> String {
+    match path {
+        serde_ignored::Path::Root => String::new(),
+        serde_ignored::Path::Seq { parent, index } => format!("{}/{}", ignored_pointer(parent), index),
+        serde_ignored::Path::Map { parent, key } => format!("{}/{}", ignored_pointer(parent), escape(key)),
+        serde_ignored::Path::Some { parent }
+        | serde_ignored::Path::NewtypeStruct { parent }
+        | serde_ignored::Path::NewtypeVariant { parent } => ignored_pointer(parent),
+    }
+}
+
+fn error_pointer(path: &serde_path_to_error::Path) -> String {
+    path.iter()
+        .filter_map(|segment| match segment {
+            serde_path_to_error::Segment::Seq { index } => Some(format!("/{}", index)),
+            serde_path_to_error::Segment::Map { key } => Some(format!("/{}", escape(key))),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Escape an object key for use in a JSON pointer
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// Where a pointer starts in the text, or else its closest ancestor that was found
+fn offset_of(offsets: &HashMap<String, usize>, pointer: &str) -> usize {
+    let mut pointer = pointer;
+    loop {
+        if let Some(offset) = offsets.get(pointer) {
+            return *offset;
+        }
+        match pointer.rsplit_once('/') {
+            Some((parent, _)) => pointer = parent,
+            None => return 0,
+        }
+    }
+}
+
+/// 1-based line and column of a byte offset
+fn line_and_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+}
+
+/// Finds where every value of a well-formed JSON text starts. Object members are
+/// located at their key, so messages point at the field name.
+struct Scanner<'a> {
+    text: &'a [u8],
+    position: usize,
+    offsets: HashMap<String, usize>,
+}
+
+impl Scanner<'_> {
+    fn locate(text: &str) -> HashMap<String, usize> {
+        let mut scanner = Scanner { text: text.as_bytes(), position: 0, offsets: HashMap::new() };
+        scanner.value(String::new());
+        scanner.offsets
+    }
+
+    fn value(&mut self, pointer: String) {
+        self.skip_whitespace();
+        self.offsets.entry(pointer.clone()).or_insert(self.position);
+        match self.peek() {
+            Some(b'{') => {
+                self.position += 1;
+                loop {
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b'"') => {
+                            let start = self.position;
+                            let member = format!("{}/{}", pointer, escape(&self.string()));
+                            self.offsets.insert(member.clone(), start);
+                            self.skip_whitespace();
+                            self.position += 1; // ':'
+                            self.value(member);
+                        }
+                        Some(b',') => self.position += 1,
+                        Some(_) => {
+                            self.position += 1;
+                            break;
+                        }
+                        None => break,
+                    }
+                }
+            }
+            Some(b'[') => {
+                self.position += 1;
+                let mut index = 0;
+                loop {
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b']') => {
+                            self.position += 1;
+                            break;
+                        }
+                        Some(b',') => self.position += 1,
+                        Some(_) => {
+                            self.value(format!("{}/{}", pointer, index));
+                            index += 1;
+                        }
+                        None => break,
+                    }
+                }
+            }
+            Some(b'"') => {
+                self.string();
+            }
+            _ => {
+                while self.peek().is_some_and(|byte| !b",]} \t\r\n".contains(&byte)) {
+                    self.position += 1;
+                }
+            }
+        }
+    }
+
+    fn string(&mut self) -> String {
+        let start = self.position;
+        self.position += 1;
+        while let Some(byte) = self.peek() {
+            self.position += if byte == b'\\' { 2 } else { 1 };
+            if byte == b'"' {
+                break;
+            }
+        }
+        let end = self.position.min(self.text.len());
+        serde_json::from_slice(&self.text[start..end]).unwrap_or_default()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|byte| byte.is_ascii_whitespace()) {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.position).copied()
+    }
+}
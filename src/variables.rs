@@ -0,0 +1,218 @@
+use anyhow::{Result, anyhow, bail};
+use serde_json::{Map, Value};
+
+/// Marks the literal slot a string variable is bound to while an XPath is converted
+const SLOT: char = '\u{E000}';
+
+/// The config's variables for a run: the declared defaults, overridden by
+/// `--var name=value`. A value that reads as JSON (`3`, `true`, `"text"`) is used as
+/// such, anything else as a string. Variables declared as null must be given.
+pub fn resolve(declared: &Map<String, Value>, overrides: &[String]) -> Result<Map<String, Value>> {
+    let mut variables = declared.clone();
+    for assignment in overrides {
+        let Some((name, text)) = assignment.split_once('=') else {
+            bail!("Invalid --var '{}': expected NAME=VALUE", assignment);
+        };
+        let name = name.trim();
+        if !variables.contains_key(name) {
+            bail!("Unknown variable '{}' in --var; declare it under 'variables' in the config", name);
+        }
+        let value = serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.to_string()));
+        variables.insert(name.to_string(), value);
+    }
+    for (name, value) in &variables {
+        match value {
+            Value::Null => bail!("Variable '{}' has no value; pass --var {}=...", name, name),
+            Value::Array(_) | Value::Object(_) => bail!("Variable '{}' must be a string, number or boolean", name),
+            _ => {}
+        }
+    }
+    Ok(variables)
+}
+
+/// An XPath with its `$name` references bound to values
+pub struct BoundXPath {
+    /// The XPath with each string variable replaced by a marked literal slot
+    pub slotted: String,
+    /// The XPath as it reads with the values written in as literals
+    pub literal: String,
+    /// The value of each slot
+    values: Vec<String>,
+    /// The variable each slot was bound from
+    names: Vec<String>,
+}
+
+impl BoundXPath {
+    /// Put the values back into a selector converted from `slotted`, as CSS strings
+    pub fn fill(&self, selector: &str) -> String {
+        let mut selector = selector.to_string();
+        for (index, value) in self.values.iter().enumerate() {
+            let slot = format!("{SLOT}{index}{SLOT}");
+            for quoted in [format!("\"{}\"", slot), format!("'{}'", slot), slot] {
+                selector = selector.replace(&quoted, &css_string(value));
+            }
+        }
+        selector
+    }
+
+    /// The first variable whose slot did not survive the conversion to `selector`, as
+    /// happens to the text of `contains(., $name)`
+    pub fn dropped(&self, selector: &str) -> Option<&str> {
+        (0..self.values.len())
+            .find(|index| !selector.contains(&format!("{SLOT}{index}{SLOT}")))
+            .map(|index| self.names[index].as_str())
+    }
+}
+
+/// Bind the `$name` references of an XPath. A number making up a whole predicate is a
+/// position and written in place; any other value fills a string literal slot, so
+/// quotes in it cannot change the expression.
+pub fn bind_xpath(xpath: &str, variables: &Map<String, Value>) -> Result<BoundXPath> {
+    let mut bound = BoundXPath { slotted: String::new(), literal: String::new(), values: Vec::new(), names: Vec::new() };
+    let mut quote = None;
+    let mut chars = xpath.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '$') if chars.peek().is_some_and(|next| next.is_alphabetic() || *next == '_') => {
+                let mut name = String::new();
+                while let Some(next) = chars.next_if(|next| next.is_alphanumeric() || matches!(next, '_' | '-')) {
+                    name.push(next);
+                }
+                let value = variables.get(&name).ok_or_else(|| anyhow!("Undefined variable '${}' in XPath '{}'", name, xpath))?;
+                let is_position = bound.slotted.trim_end().ends_with('[') && chars.clone().find(|next| !next.is_whitespace()) == Some(']');
+                if let (Value::Number(number), true) = (value, is_position) {
+                    bound.slotted.push_str(&number.to_string());
+                    bound.literal.push_str(&number.to_string());
+                } else {
+                    let text = match value {
+                        Value::String(text) => text.clone(),
+                        other => other.to_string(),
+                    };
+                    bound.slotted.push_str(&format!("'{SLOT}{}{SLOT}'", bound.values.len()));
+                    bound.literal.push_str(&xpath_literal(&text));
+                    bound.values.push(text);
+                    bound.names.push(name);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        bound.slotted.push(c);
+        bound.literal.push(c);
+    }
+    Ok(bound)
+}
+
+/// An XPath string literal for any text, using concat() when it holds both quote kinds
+fn xpath_literal(text: &str) -> String {
+    if !text.contains('\'') {
+        format!("'{}'", text)
+    } else if !text.contains('"') {
+        format!("\"{}\"", text)
+    } else {
+        let parts: Vec<String> = text.split('\'').map(|part| format!("'{}'", part)).collect();
+        format!("concat({})", parts.join(", \"'\", "))
+    }
+}
+
+/// A double-quoted CSS string
+fn css_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\a "),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn variables(value: Value) -> Map<String, Value> {
+        let Value::Object(variables) = value else {
+            panic!("variables must be an object");
+        };
+        variables
+    }
+
+    fn overrides(assignments: &[&str]) -> Vec<String> {
+        assignments.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn overrides_are_read_as_json_when_they_parse_and_as_text_otherwise() {
+        let declared = variables(json!({ "row": 1, "title": "Calendar", "flag": false }));
+        let resolved = resolve(&declared, &overrides(&["row=3", "title=Bob's list", "flag=true"])).unwrap();
+        assert_eq!(Value::Object(resolved), json!({ "row": 3, "title": "Bob's list", "flag": true }));
+    }
+
+    #[test]
+    fn only_declared_variables_with_values_can_be_used() {
+        let declared = variables(json!({ "section": null }));
+        assert_eq!(resolve(&declared, &[]).unwrap_err().to_string(), "Variable 'section' has no value; pass --var section=...");
+        assert!(resolve(&declared, &overrides(&["section=Europe"])).is_ok());
+        assert!(resolve(&declared, &overrides(&["other=1"])).unwrap_err().to_string().starts_with("Unknown variable 'other'"));
+        assert!(resolve(&declared, &overrides(&["section"])).unwrap_err().to_string().starts_with("Invalid --var"));
+        assert!(resolve(&variables(json!({ "list": [1] })), &[]).is_err());
+    }
+
+    #[test]
+    fn string_values_are_written_as_literals_whatever_quotes_they_hold() {
+        let bound = bind_xpath("//h1[. = $title]", &variables(json!({ "title": "Bob's \"Top\" Picks" }))).unwrap();
+        assert_eq!(bound.literal, r#"//h1[. = concat('Bob', "'", 's "Top" Picks')]"#);
+
+        let bound = bind_xpath("//h1[. = $title]", &variables(json!({ "title": "Bob's" }))).unwrap();
+        assert_eq!(bound.literal, r#"//h1[. = "Bob's"]"#);
+    }
+
+    #[test]
+    fn string_values_are_filled_in_as_escaped_css_strings() {
+        let bound = bind_xpath("//table[@summary=$summary]", &variables(json!({ "summary": "Bob's \"Top\"\\\nPicks" }))).unwrap();
+        assert_eq!(bound.fill("table[summary=\u{E000}0\u{E000}]"), r#"table[summary="Bob's \"Top\"\\\a Picks"]"#);
+        assert_eq!(bound.fill("table[summary='\u{E000}0\u{E000}']"), r#"table[summary="Bob's \"Top\"\\\a Picks"]"#);
+    }
+
+    #[test]
+    fn numbers_are_positions_only_when_they_make_up_a_whole_predicate() {
+        let numbers = variables(json!({ "row": 3, "width": 600 }));
+        let bound = bind_xpath("//tr[ $row ]/td[@width=$width]", &numbers).unwrap();
+        assert_eq!(bound.literal, "//tr[ 3 ]/td[@width='600']");
+        assert_eq!(bound.slotted, "//tr[ 3 ]/td[@width='\u{E000}0\u{E000}']");
+    }
+
+    #[test]
+    fn dollar_signs_inside_literals_are_text() {
+        let bound = bind_xpath("//td[. = '$5']", &Map::new()).unwrap();
+        assert_eq!(bound.literal, "//td[. = '$5']");
+        assert_eq!(bound.slotted, bound.literal);
+    }
+
+    #[test]
+    fn undeclared_variables_are_an_error() {
+        let error = bind_xpath("//tr[$row]", &Map::new()).err().unwrap();
+        assert_eq!(error.to_string(), "Undefined variable '$row' in XPath '//tr[$row]'");
+    }
+
+    #[test]
+    fn variables_the_css_conversion_drops_are_reported() {
+        let title = variables(json!({ "title": "Ex-Dividend Calendar", "width": 600 }));
+        let bound = bind_xpath("//table[@width=$width][contains(., $title)]//th", &title).unwrap();
+        assert_eq!(bound.dropped("table[width=\u{E000}0\u{E000}] th"), Some("title"));
+        assert_eq!(bound.dropped("table[width=\u{E000}0\u{E000}][title=\u{E000}1\u{E000}] th"), None);
+
+        let error = crate::xpath_to_css_selector("//table[contains(., $title)]//th", &title).unwrap_err();
+        assert!(error.to_string().starts_with("Variable '$title' in XPath"));
+        assert_eq!(crate::xpath_to_css_selector("//table[@width=$width]//th", &title).unwrap(), r#"table[width="600"] th"#);
+    }
+}